@@ -0,0 +1,41 @@
+//! Bakes the git commit into the binary for GET /version and the startup
+//! log. CI passes it as GIT_SHA; local builds ask git, and fall back to
+//! "unknown" outside a checkout (e.g. the Docker build context).
+
+use std::{path::Path, process::Command};
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_SHA");
+    watch_git_head();
+
+    let sha = std::env::var("GIT_SHA")
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+        .or_else(|| {
+            let out = Command::new("git").args(["rev-parse", "--short", "HEAD"]).output().ok()?;
+            out.status.success().then(|| String::from_utf8_lossy(&out.stdout).trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".into());
+    println!("cargo:rustc-env=GIT_SHA={sha}");
+}
+
+/// Rerun when HEAD moves: on a branch switch (.git/HEAD) and on a new
+/// commit to the branch it names (its ref file, or packed-refs once git
+/// packs it).  Only existing paths are watched, since cargo reruns on every
+/// build for a missing one.
+fn watch_git_head() {
+    let head = Path::new(".git/HEAD");
+    if !head.exists() {
+        return;
+    }
+    println!("cargo:rerun-if-changed=.git/HEAD");
+
+    let branch_ref = std::fs::read_to_string(head)
+        .ok()
+        .and_then(|h| h.strip_prefix("ref: ").map(|r| Path::new(".git").join(r.trim())));
+    for path in branch_ref.iter().map(|p| p.as_path()).chain([Path::new(".git/packed-refs")]) {
+        if path.exists() {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+    }
+}
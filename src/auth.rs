@@ -0,0 +1,51 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use tracing::warn;
+
+use crate::AppState;
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Bearer-token authentication
+// ─────────────────────────────────────────────────────────────────────────────
+//
+// Enabled by WEBHOOK_AUTH_TOKEN(_FILE).  Layered onto every route except the
+// probes, so kubelet can keep calling /healthz and /readyz without the token.
+
+/// Reject requests without `Authorization: Bearer <token>` when a token is
+/// configured; pass everything through otherwise.
+pub async fn require_bearer(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let Some(expected) = state.cfg.webhook_auth_token.as_deref() else {
+        return next.run(req).await;
+    };
+
+    let presented = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    match presented {
+        Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => {
+            next.run(req).await
+        }
+        _ => {
+            warn!("{} {}: missing or invalid bearer token", req.method(), req.uri().path());
+            (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+                Json(serde_json::json!({"error": "unauthorized"})),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Compare without short-circuiting so response timing doesn't reveal how
+/// much of the token matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
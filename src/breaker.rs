@@ -0,0 +1,133 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Circuit breaker around PowerDNS
+// ─────────────────────────────────────────────────────────────────────────────
+//
+//   closed ──(threshold consecutive failures)──▶ open
+//   open   ──(cooldown elapsed)────────────────▶ half-open
+//   half-open ──(success)──▶ closed
+//   half-open ──(failure)──▶ open (cooldown restarts)
+//
+// While open every call fails immediately with `CircuitOpen` instead of
+// adding load to a backend that is already struggling.  Half-open lets a
+// single probe through; other calls keep failing fast until it reports.
+// Failures only count as consecutive while each follows the last within the
+// window, so a slow trickle of errors over hours never opens the breaker.
+
+/// Returned instead of calling PowerDNS while the breaker is open.
+#[derive(Debug, thiserror::Error)]
+#[error("PowerDNS circuit breaker is open after repeated failures; retry in {}s", retry_in.as_secs().max(1))]
+pub struct CircuitOpen {
+    pub retry_in: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    /// Consecutive failures that open the breaker; 0 disables it.
+    threshold: u32,
+    cooldown: Duration,
+    /// Longest gap between failures that still counts as consecutive; zero
+    /// for no limit.
+    window: Duration,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    failures: u32,
+    last_failure: Option<Instant>,
+    opened_at: Option<Instant>,
+    /// When the half-open probe was let through.  A probe that never
+    /// reports (its request was cancelled) stops blocking others after a
+    /// cooldown.
+    probe_started: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration, window: Duration) -> Self {
+        Self { threshold, cooldown, window, inner: Mutex::new(Inner::default()) }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        let inner = self.inner.lock().unwrap();
+        match inner.opened_at {
+            None => BreakerState::Closed,
+            Some(at) if at.elapsed() < self.cooldown => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+
+    /// Fail fast if the breaker is open.  Once the cooldown has elapsed one
+    /// call is let through to probe whether PowerDNS has recovered.
+    pub fn check(&self) -> Result<(), CircuitOpen> {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(at) = inner.opened_at else {
+            return Ok(());
+        };
+        let elapsed = at.elapsed();
+        if elapsed < self.cooldown {
+            return Err(CircuitOpen { retry_in: self.cooldown.saturating_sub(elapsed) });
+        }
+        if let Some(started) = inner.probe_started {
+            let probing = started.elapsed();
+            if probing < self.cooldown {
+                return Err(CircuitOpen { retry_in: self.cooldown.saturating_sub(probing) });
+            }
+        }
+        inner.probe_started = Some(Instant::now());
+        Ok(())
+    }
+
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.opened_at.take().is_some() {
+            info!("PowerDNS circuit breaker closed");
+        }
+        inner.probe_started = None;
+        inner.failures = 0;
+        inner.last_failure = None;
+    }
+
+    pub fn record_failure(&self) {
+        if self.threshold == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+        let stale = inner
+            .last_failure
+            .is_some_and(|last| !self.window.is_zero() && now.duration_since(last) > self.window);
+        if stale && inner.opened_at.is_none() {
+            inner.failures = 0;
+        }
+        inner.last_failure = Some(now);
+        inner.failures = inner.failures.saturating_add(1);
+        inner.probe_started = None;
+        if inner.failures >= self.threshold {
+            if inner.opened_at.is_none() {
+                warn!(
+                    "PowerDNS circuit breaker opened after {} consecutive failures; \
+                     failing fast for {}s",
+                    inner.failures,
+                    self.cooldown.as_secs()
+                );
+            }
+            inner.opened_at = Some(now);
+        }
+    }
+}
@@ -1,5 +1,9 @@
+use regex::Regex;
+use reqwest::header::HeaderName;
 use serde::Deserialize;
-use std::path::Path;
+use std::{net::IpAddr, path::Path};
+
+use crate::dns::name_to_ascii;
 
 // ─────────────────────────────────────────────────────────────────────────────
 // Raw env-var config (non-sensitive values only)
@@ -8,11 +12,13 @@ use std::path::Path;
 /// Non-sensitive configuration loaded from environment variables.
 #[derive(Debug, Clone, Deserialize)]
 struct RawConfig {
-    /// Base URL of the PowerDNS HTTP API, e.g. http://powerdns:8081
+    /// Base URL of the PowerDNS HTTP API, e.g. http://powerdns:8081; may
+    /// include a reverse-proxy path prefix, e.g. https://gw/powerdns
     #[serde(default = "default_pdns_url")]
     pub pdns_api_url: String,
 
-    /// PowerDNS server-id, almost always "localhost"
+    /// PowerDNS server-id, almost always "localhost"; a comma-separated
+    /// list routes each name to whichever server hosts its zone
     #[serde(default = "default_server_id")]
     pub pdns_server_id: String,
 
@@ -20,14 +26,254 @@ struct RawConfig {
     #[serde(default)]
     pub domain_filter: String,
 
+    /// Comma-separated subdomains excluded from management even when they
+    /// fall under DOMAIN_FILTER
+    #[serde(default)]
+    pub exclude_domains: String,
+
+    /// Regex a name must match to be managed, like external-dns
+    /// --regex-domain-filter; applied on top of DOMAIN_FILTER
+    #[serde(default)]
+    pub domain_filter_regex: String,
+
+    /// Regex whose matches are never managed, like external-dns
+    /// --regex-domain-exclusion
+    #[serde(default)]
+    pub domain_filter_regex_exclusion: String,
+
+    /// Comma-separated list of PowerDNS zone accounts to manage; empty = any
+    #[serde(default)]
+    pub pdns_account_filter: String,
+
     /// Default TTL when the endpoint doesn't specify one
     #[serde(default = "default_ttl")]
     pub default_ttl: u32,
 
+    /// Keep the TTL an existing RRset has when an update carries no TTL,
+    /// instead of resetting it to DEFAULT_TTL
+    #[serde(default)]
+    pub preserve_existing_ttl: bool,
+
+    /// Lowest TTL written; lower TTLs are raised to it (0 = no minimum)
+    #[serde(default)]
+    pub min_ttl: u32,
+
+    /// Highest TTL written; higher TTLs are lowered to it (0 = no maximum)
+    #[serde(default)]
+    pub max_ttl: u32,
+
     /// Port to listen on
     #[serde(default = "default_port")]
     pub port: u16,
 
+    /// IP address to listen on, IPv4 or IPv6 (e.g. 127.0.0.1 or ::)
+    #[serde(default = "default_bind_address")]
+    pub bind_address: String,
+
+    /// Skip (with a warning) changes for names outside the domain filter;
+    /// false rejects the whole request instead
+    #[serde(default = "default_true")]
+    pub skip_out_of_filter_changes: bool,
+
+    /// Merge endpoints sharing a name and type within one change bucket into
+    /// a single RRset instead of letting each one REPLACE the previous
+    #[serde(default = "default_true")]
+    pub merge_same_name_endpoints: bool,
+
+    /// Which TTL a merged RRset gets when its endpoints disagree
+    #[serde(default)]
+    pub merged_ttl_strategy: MergedTtlStrategy,
+
+    /// What POST /records does when a zone's PATCH fails part-way through
+    /// a batch
+    #[serde(default)]
+    pub apply_mode: ApplyMode,
+
+    /// Log the PATCHes and zone creations POST /records would send instead
+    /// of sending them
+    #[serde(default)]
+    pub dry_run: bool,
+
+    /// After a zone is patched, ask PowerDNS to send NOTIFYs to its
+    /// secondaries (PUT /zones/:id/notify)
+    #[serde(default)]
+    pub notify_after_apply: bool,
+
+    /// After a zone is patched, rectify it (PUT /zones/:id/rectify), as
+    /// DNSSEC-signed zones need
+    #[serde(default)]
+    pub rectify_after_apply: bool,
+
+    /// Attach an "external-dns create"/"external-dns update" comment to every
+    /// RRset written, so PowerDNS-side auditing can see the intent
+    #[serde(default)]
+    pub audit_intent_comments: bool,
+
+    /// Set PowerDNS's `set-ptr` flag on A/AAAA records so it maintains the
+    /// matching reverse records
+    #[serde(default)]
+    pub set_ptr_for_forward: bool,
+
+    /// providerSpecific key whose value /adjustendpoints uses as the HTTPS
+    /// target, i.e. the suffix of the provider-specific-webhook annotation
+    #[serde(default = "default_https_annotation")]
+    pub https_target_annotation: String,
+
+    /// Target for an HTTPS endpoint that arrives at /adjustendpoints with no
+    /// annotation and no targets, e.g. "1 . alpn=h2,h3"; empty = skip it
+    #[serde(default)]
+    pub default_https_target: String,
+
+    /// Reject HTTPS/SVCB records whose final TargetName is neither "." nor a
+    /// valid hostname, or whose SvcParams are unknown or malformed, instead
+    /// of passing them on to PowerDNS
+    #[serde(default = "default_true")]
+    pub validate_https_targets: bool,
+
+    /// Report records whose TTL equals DEFAULT_TTL with no TTL on read, so
+    /// they compare equal to sources that don't set one
+    #[serde(default)]
+    pub omit_default_ttl_on_read: bool,
+
+    /// PowerDNS zone metadata kind (e.g. "X-EXTERNAL-DNS-SUSPENDED") that,
+    /// when set to 1/true/yes on a zone, removes it from management
+    #[serde(default)]
+    pub zone_suspend_metadata_kind: String,
+
+    /// Comma-separated HTTP status codes to treat as retryable in addition
+    /// to the built-in 429 and 5xx, e.g. "409"
+    #[serde(default)]
+    pub pdns_retryable_statuses: String,
+
+    /// Overall timeout for one PowerDNS API request
+    #[serde(default = "default_request_timeout_secs")]
+    pub pdns_request_timeout_secs: u64,
+
+    /// Timeout for establishing the connection to PowerDNS
+    #[serde(default = "default_connect_timeout_secs")]
+    pub pdns_connect_timeout_secs: u64,
+
+    /// Talk HTTP/2 to PowerDNS with prior knowledge (no upgrade or ALPN), for
+    /// an HTTP/2-capable PowerDNS proxy; HTTP/1.1 otherwise
+    #[serde(default)]
+    pub pdns_http2: bool,
+
+    /// Idle connections kept open to PowerDNS; unlimited by default, as in
+    /// reqwest
+    #[serde(default = "default_pool_max_idle")]
+    pub pdns_pool_max_idle_per_host: usize,
+
+    /// How long an idle PowerDNS connection is kept before it is closed;
+    /// 0 keeps it until PowerDNS closes it
+    #[serde(default = "default_pool_idle_secs")]
+    pub pdns_pool_idle_timeout_secs: u64,
+
+    /// Retries for a transient PowerDNS failure on zone reads and PATCHes;
+    /// 0 disables retrying
+    #[serde(default = "default_max_retries")]
+    pub pdns_max_retries: u32,
+
+    /// Backoff before the first retry, doubled for each further one
+    #[serde(default = "default_retry_base_ms")]
+    pub pdns_retry_base_ms: u64,
+
+    /// Maximum RRset changes listed in a /records/preview response; the
+    /// rest are summarised as "...N more changes omitted"
+    #[serde(default = "default_preview_max_changes")]
+    pub preview_max_changes: usize,
+
+    /// Consecutive PowerDNS failures (connection errors / 5xx) that open the
+    /// circuit breaker; 0 disables it.  PDNS_CB_THRESHOLD is accepted too.
+    #[serde(default = "default_cb_failure_threshold", alias = "pdns_cb_threshold")]
+    pub pdns_cb_failure_threshold: u32,
+
+    /// How long the breaker stays open before letting a probe request
+    /// through.  PDNS_CB_COOLDOWN_SECS is accepted too.
+    #[serde(default = "default_cb_cooldown_seconds", alias = "pdns_cb_cooldown_secs")]
+    pub pdns_cb_cooldown_seconds: u64,
+
+    /// Window the consecutive failures must fall within to open the breaker;
+    /// a failure after a longer quiet spell starts the count again.  0 counts
+    /// failures however far apart they are
+    #[serde(default = "default_cb_window_seconds")]
+    pub pdns_cb_window_seconds: u64,
+
+    /// Create a missing zone when an endpoint under DOMAIN_FILTER has none;
+    /// the zone is named after the DOMAIN_FILTER entry the endpoint falls under
+    #[serde(default)]
+    pub auto_create_zones: bool,
+
+    /// Kind of zone AUTO_CREATE_ZONES creates
+    #[serde(default)]
+    pub auto_create_zone_kind: ZoneKind,
+
+    /// Manage Slave and Consumer zones too; they are read-only replicas that
+    /// PowerDNS refuses to PATCH, so they are skipped by default
+    #[serde(default)]
+    pub manage_slave_zones: bool,
+
+    /// Seconds the PowerDNS zone list is reused before being fetched again;
+    /// 0 fetches it on every use
+    #[serde(default = "default_zone_cache_ttl")]
+    pub zone_cache_ttl: u64,
+
+    /// How many zones GET /records fetches from PowerDNS in parallel
+    #[serde(default = "default_fetch_concurrency")]
+    pub pdns_fetch_concurrency: usize,
+
+    /// Warn when PowerDNS lists more zones than this, as GET /records reads
+    /// every one of them; 0 disables the warning
+    #[serde(default = "default_zone_count_warn")]
+    pub zone_count_warn: usize,
+
+    /// Fetch the PowerDNS server object at startup and refuse to start if
+    /// the URL, server id or API key is wrong
+    #[serde(default = "default_true")]
+    pub startup_pdns_check: bool,
+
+    /// At startup, refuse to start if a DOMAIN_FILTER entry matches no
+    /// PowerDNS zone, e.g. because of a typo
+    #[serde(default)]
+    pub validate_domain_filter: bool,
+
+    /// Seconds a webhook request may take before it is answered with 504;
+    /// 0 disables the limit
+    #[serde(default = "default_handler_timeout_secs")]
+    pub handler_timeout_secs: u64,
+
+    /// Largest request body accepted, in bytes; bigger ones get 413
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+
+    /// Request bodies larger than this are logged at DEBUG only up to this
+    /// many bytes, without pretty-printing; 0 logs every body in full
+    #[serde(default = "default_log_body_max_bytes")]
+    pub log_body_max_bytes: usize,
+
+    /// Serve debug-only endpoints (GET /zones) that aren't part of the
+    /// external-dns webhook API
+    #[serde(default)]
+    pub enable_debug_endpoints: bool,
+
+    /// PEM certificate chain and private key for serving the webhook over
+    /// TLS; both or neither must be set
+    #[serde(default)]
+    pub tls_cert_file: String,
+    #[serde(default)]
+    pub tls_key_file: String,
+
+    /// PEM client certificate and PKCS#8 private key presented to PowerDNS
+    /// (e.g. an mTLS reverse proxy); both or neither must be set
+    #[serde(default)]
+    pub pdns_client_cert_file: String,
+    #[serde(default)]
+    pub pdns_client_key_file: String,
+
+    /// PEM CA certificate trusted for the PowerDNS connection, in addition
+    /// to the system roots
+    #[serde(default)]
+    pub pdns_ca_file: String,
+
     // ── Secret resolution ────────────────────────────────────────────────────
     //
     // Secrets are loaded from files, not plain env vars.
@@ -44,6 +290,76 @@ struct RawConfig {
     /// Inline API key – used only when PDNS_API_KEY_FILE does not exist.
     #[serde(default)]
     pub pdns_api_key: String,
+
+    /// Seconds between re-reads of PDNS_API_KEY_FILE, so a rotated secret
+    /// is picked up without a restart.  0 disables reloading.
+    #[serde(default = "default_api_key_reload_secs")]
+    pub pdns_api_key_reload_secs: u64,
+
+    /// Header the API key is sent in, for gateways in front of PowerDNS
+    /// that expect another one.
+    #[serde(default = "default_api_key_header")]
+    pub pdns_api_key_header: String,
+
+    // Optional bearer token external-dns must present to the webhook.
+    // Same fallback order as the API key; leaving both unset disables auth.
+
+    /// Path to a file that contains the webhook bearer token.
+    #[serde(default)]
+    pub webhook_auth_token_file: String,
+
+    /// Inline bearer token – used only when WEBHOOK_AUTH_TOKEN_FILE does not
+    /// exist.
+    #[serde(default)]
+    pub webhook_auth_token: String,
+}
+
+/// How to pick the TTL when same-name endpoints merged into one RRset carry
+/// different TTLs.  Endpoints without a TTL (0) never win a conflict.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergedTtlStrategy {
+    /// Keep the TTL of the first endpoint that set one.
+    #[default]
+    First,
+    /// Use the lowest TTL.
+    Min,
+    /// Use the highest TTL.
+    Max,
+}
+
+/// How POST /records handles a failed PATCH when a batch spans several
+/// zones.  Each zone's PATCH is atomic in PowerDNS either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ApplyMode {
+    /// Stop at the first failure; zones already patched stay patched.
+    #[default]
+    BestEffort,
+    /// Stop at the first failure and re-PATCH the zones already patched back
+    /// to the RRsets they had before the batch.
+    Atomic,
+    /// Attempt every endpoint and report all the ones that failed.
+    Continue,
+}
+
+/// PowerDNS zone kind for zones created by `AUTO_CREATE_ZONES`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum ZoneKind {
+    #[default]
+    #[serde(alias = "native")]
+    Native,
+    #[serde(alias = "master")]
+    Master,
+}
+
+impl ZoneKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Native => "Native",
+            Self::Master => "Master",
+        }
+    }
 }
 
 // ─────────────────────────────────────────────────────────────────────────────
@@ -52,13 +368,78 @@ struct RawConfig {
 
 #[derive(Debug, Clone)]
 pub struct Config {
+    /// PDNS_API_URL without trailing slashes or `/api/v1`; any path prefix
+    /// it has is kept.
     pub pdns_api_url: String,
     /// The resolved API key – never stored in an env var at runtime.
     pub pdns_api_key: String,
+    /// The file the API key was read from; `None` when it came from the
+    /// inline PDNS_API_KEY, which is never reloaded.
+    pub pdns_api_key_file: Option<String>,
+    pub pdns_api_key_reload_secs: u64,
+    /// PDNS_API_KEY_HEADER, validated at load time.
+    pub pdns_api_key_header: HeaderName,
+    /// Bearer token required on webhook requests, if auth is enabled.
+    pub webhook_auth_token: Option<String>,
     pub pdns_server_id: String,
     pub domain_filter: String,
+    pub exclude_domains: String,
+    /// Compiled DOMAIN_FILTER_REGEX, if set.
+    pub domain_filter_regex: Option<Regex>,
+    /// Compiled DOMAIN_FILTER_REGEX_EXCLUSION, if set.
+    pub domain_filter_regex_exclusion: Option<Regex>,
+    pub pdns_account_filter: String,
     pub default_ttl: u32,
+    pub preserve_existing_ttl: bool,
+    pub min_ttl: u32,
+    pub max_ttl: u32,
     pub port: u16,
+    pub bind_address: IpAddr,
+    pub skip_out_of_filter_changes: bool,
+    pub merge_same_name_endpoints: bool,
+    pub merged_ttl_strategy: MergedTtlStrategy,
+    pub apply_mode: ApplyMode,
+    pub dry_run: bool,
+    pub notify_after_apply: bool,
+    pub rectify_after_apply: bool,
+    pub audit_intent_comments: bool,
+    pub set_ptr_for_forward: bool,
+    pub https_target_annotation: String,
+    /// DEFAULT_HTTPS_TARGET, if set.
+    pub default_https_target: Option<String>,
+    pub validate_https_targets: bool,
+    pub omit_default_ttl_on_read: bool,
+    pub zone_suspend_metadata_kind: String,
+    pub preview_max_changes: usize,
+    /// Extra retryable statuses, validated at load time.
+    pub pdns_retryable_statuses: Vec<u16>,
+    pub pdns_request_timeout_secs: u64,
+    pub pdns_connect_timeout_secs: u64,
+    pub pdns_http2: bool,
+    pub pdns_pool_max_idle_per_host: usize,
+    pub pdns_pool_idle_timeout_secs: u64,
+    pub pdns_max_retries: u32,
+    pub pdns_retry_base_ms: u64,
+    pub pdns_cb_failure_threshold: u32,
+    pub pdns_cb_cooldown_seconds: u64,
+    pub pdns_cb_window_seconds: u64,
+    pub auto_create_zones: bool,
+    pub auto_create_zone_kind: ZoneKind,
+    pub manage_slave_zones: bool,
+    pub zone_cache_ttl: u64,
+    pub pdns_fetch_concurrency: usize,
+    pub zone_count_warn: usize,
+    pub startup_pdns_check: bool,
+    pub validate_domain_filter: bool,
+    pub handler_timeout_secs: u64,
+    pub max_body_bytes: usize,
+    pub log_body_max_bytes: usize,
+    pub enable_debug_endpoints: bool,
+    pub tls_cert_file: String,
+    pub tls_key_file: String,
+    pub pdns_client_cert_file: String,
+    pub pdns_client_key_file: String,
+    pub pdns_ca_file: String,
 }
 
 impl Config {
@@ -70,24 +451,179 @@ impl Config {
     /// If that file does not exist the `PDNS_API_KEY` env var is used as a
     /// fallback so local development still works without a mounted secret.
     pub fn from_env() -> anyhow::Result<Self> {
-        let raw: RawConfig = envy::from_env()?;
+        Self::from_vars(std::env::vars())
+    }
+
+    /// Load configuration from an explicit set of `(NAME, value)` pairs
+    /// instead of the process environment.  Used by `from_env` and by tests.
+    pub fn from_vars<I>(vars: I) -> anyhow::Result<Self>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let raw: RawConfig = envy::from_iter(vars)?;
 
         let pdns_api_key = resolve_secret(
             &raw.pdns_api_key_file,
             &raw.pdns_api_key,
             "PDNS_API_KEY",
         )?;
+        let pdns_api_key_file = Some(raw.pdns_api_key_file.clone())
+            .filter(|f| Path::new(f).exists());
+
+        let webhook_auth_token =
+            if raw.webhook_auth_token_file.is_empty() && raw.webhook_auth_token.is_empty() {
+                None
+            } else {
+                Some(resolve_secret(
+                    &raw.webhook_auth_token_file,
+                    &raw.webhook_auth_token,
+                    "WEBHOOK_AUTH_TOKEN",
+                )?)
+            };
+
+        let pdns_api_key_header = HeaderName::from_bytes(raw.pdns_api_key_header.trim().as_bytes())
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "PDNS_API_KEY_HEADER: '{}' is not a valid header name",
+                    raw.pdns_api_key_header
+                )
+            })?;
+
+        let pdns_api_url = parse_api_url(&raw.pdns_api_url)
+            .map_err(|e| anyhow::anyhow!("PDNS_API_URL: {e}"))?;
+
+        let bind_address: IpAddr = raw.bind_address.trim().parse().map_err(|_| {
+            anyhow::anyhow!("BIND_ADDRESS: '{}' is not an IP address", raw.bind_address)
+        })?;
+
+        let domain_filter_regex =
+            parse_regex(&raw.domain_filter_regex, "DOMAIN_FILTER_REGEX")?;
+        let domain_filter_regex_exclusion = parse_regex(
+            &raw.domain_filter_regex_exclusion,
+            "DOMAIN_FILTER_REGEX_EXCLUSION",
+        )?;
+
+        let pdns_retryable_statuses = parse_status_list(&raw.pdns_retryable_statuses)
+            .map_err(|e| anyhow::anyhow!("PDNS_RETRYABLE_STATUSES: {e}"))?;
+
+        if raw.max_ttl > 0 && raw.min_ttl > raw.max_ttl {
+            anyhow::bail!("MIN_TTL ({}) is greater than MAX_TTL ({})", raw.min_ttl, raw.max_ttl);
+        }
+        if raw.tls_cert_file.is_empty() != raw.tls_key_file.is_empty() {
+            anyhow::bail!("TLS_CERT_FILE and TLS_KEY_FILE must be set together");
+        }
+        if raw.pdns_client_cert_file.is_empty() != raw.pdns_client_key_file.is_empty() {
+            anyhow::bail!("PDNS_CLIENT_CERT_FILE and PDNS_CLIENT_KEY_FILE must be set together");
+        }
+        if raw.auto_create_zones && raw.domain_filter.trim().is_empty() {
+            anyhow::bail!("AUTO_CREATE_ZONES requires DOMAIN_FILTER");
+        }
+        if raw.pdns_server_id.split(',').all(|s| s.trim().is_empty()) {
+            anyhow::bail!("PDNS_SERVER_ID must name at least one server");
+        }
+        if raw.max_body_bytes == 0 {
+            anyhow::bail!("MAX_BODY_BYTES must be greater than 0");
+        }
+        if raw.pdns_request_timeout_secs == 0 {
+            anyhow::bail!("PDNS_REQUEST_TIMEOUT_SECS must be greater than 0");
+        }
+        if raw.pdns_connect_timeout_secs == 0 {
+            anyhow::bail!("PDNS_CONNECT_TIMEOUT_SECS must be greater than 0");
+        }
 
         Ok(Self {
-            pdns_api_url: raw.pdns_api_url,
+            pdns_api_url,
             pdns_api_key,
+            pdns_api_key_file,
+            pdns_api_key_reload_secs: raw.pdns_api_key_reload_secs,
+            pdns_api_key_header,
+            webhook_auth_token,
             pdns_server_id: raw.pdns_server_id,
             domain_filter: raw.domain_filter,
+            exclude_domains: raw.exclude_domains,
+            domain_filter_regex,
+            domain_filter_regex_exclusion,
+            pdns_account_filter: raw.pdns_account_filter,
             default_ttl: raw.default_ttl,
+            preserve_existing_ttl: raw.preserve_existing_ttl,
+            min_ttl: raw.min_ttl,
+            max_ttl: raw.max_ttl,
             port: raw.port,
+            bind_address,
+            skip_out_of_filter_changes: raw.skip_out_of_filter_changes,
+            merge_same_name_endpoints: raw.merge_same_name_endpoints,
+            merged_ttl_strategy: raw.merged_ttl_strategy,
+            apply_mode: raw.apply_mode,
+            dry_run: raw.dry_run,
+            notify_after_apply: raw.notify_after_apply,
+            rectify_after_apply: raw.rectify_after_apply,
+            audit_intent_comments: raw.audit_intent_comments,
+            set_ptr_for_forward: raw.set_ptr_for_forward,
+            https_target_annotation: raw.https_target_annotation,
+            default_https_target: Some(raw.default_https_target.trim().to_string())
+                .filter(|t| !t.is_empty()),
+            validate_https_targets: raw.validate_https_targets,
+            omit_default_ttl_on_read: raw.omit_default_ttl_on_read,
+            zone_suspend_metadata_kind: raw.zone_suspend_metadata_kind,
+            preview_max_changes: raw.preview_max_changes,
+            pdns_retryable_statuses,
+            pdns_request_timeout_secs: raw.pdns_request_timeout_secs,
+            pdns_connect_timeout_secs: raw.pdns_connect_timeout_secs,
+            pdns_http2: raw.pdns_http2,
+            pdns_pool_max_idle_per_host: raw.pdns_pool_max_idle_per_host,
+            pdns_pool_idle_timeout_secs: raw.pdns_pool_idle_timeout_secs,
+            pdns_max_retries: raw.pdns_max_retries,
+            pdns_retry_base_ms: raw.pdns_retry_base_ms,
+            pdns_cb_failure_threshold: raw.pdns_cb_failure_threshold,
+            pdns_cb_cooldown_seconds: raw.pdns_cb_cooldown_seconds,
+            pdns_cb_window_seconds: raw.pdns_cb_window_seconds,
+            auto_create_zones: raw.auto_create_zones,
+            auto_create_zone_kind: raw.auto_create_zone_kind,
+            manage_slave_zones: raw.manage_slave_zones,
+            zone_cache_ttl: raw.zone_cache_ttl,
+            pdns_fetch_concurrency: raw.pdns_fetch_concurrency,
+            zone_count_warn: raw.zone_count_warn,
+            startup_pdns_check: raw.startup_pdns_check,
+            validate_domain_filter: raw.validate_domain_filter,
+            handler_timeout_secs: raw.handler_timeout_secs,
+            max_body_bytes: raw.max_body_bytes,
+            log_body_max_bytes: raw.log_body_max_bytes,
+            enable_debug_endpoints: raw.enable_debug_endpoints,
+            tls_cert_file: raw.tls_cert_file,
+            tls_key_file: raw.tls_key_file,
+            pdns_client_cert_file: raw.pdns_client_cert_file,
+            pdns_client_key_file: raw.pdns_client_key_file,
+            pdns_ca_file: raw.pdns_ca_file,
         })
     }
 
+    /// URL of a PowerDNS server object, e.g.
+    /// `http://powerdns:8081/api/v1/servers/localhost`.  Every API call is
+    /// made below one.
+    pub fn server_url(&self, server_id: &str) -> String {
+        format!("{}/api/v1/servers/{}", self.pdns_api_url, server_id)
+    }
+
+    /// The configured PowerDNS server ids, in PDNS_SERVER_ID order.  Zones
+    /// auto-created by `AUTO_CREATE_ZONES` go on the first.
+    pub fn server_ids(&self) -> Vec<String> {
+        self.pdns_server_id
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    /// The TLS certificate and key paths, if the webhook should serve TLS.
+    pub fn tls_files(&self) -> Option<(&str, &str)> {
+        if self.tls_cert_file.is_empty() {
+            None
+        } else {
+            Some((&self.tls_cert_file, &self.tls_key_file))
+        }
+    }
+
     /// Return the domain filter as a `Vec<String>`, empty if unconfigured.
     pub fn domain_filter_list(&self) -> Vec<String> {
         self.domain_filter
@@ -97,6 +633,150 @@ impl Config {
             .map(String::from)
             .collect()
     }
+
+    /// Return the excluded domains as a `Vec<String>`, empty if unconfigured.
+    pub fn exclude_domains_list(&self) -> Vec<String> {
+        self.exclude_domains
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    /// Return the account filter as a `Vec<String>`, empty if unconfigured.
+    pub fn account_filter_list(&self) -> Vec<String> {
+        self.pdns_account_filter
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    /// Clamp `ttl` into `MIN_TTL..=MAX_TTL`, ignoring unset bounds.
+    pub fn clamp_ttl(&self, ttl: u32) -> u32 {
+        let ttl = ttl.max(self.min_ttl);
+        if self.max_ttl > 0 { ttl.min(self.max_ttl) } else { ttl }
+    }
+
+    /// Whether a PowerDNS response with `status` is worth retrying: 429, any
+    /// 5xx, or one of `PDNS_RETRYABLE_STATUSES`.
+    pub fn is_retryable_status(&self, status: u16) -> bool {
+        status == 429
+            || (500..=599).contains(&status)
+            || self.pdns_retryable_statuses.contains(&status)
+    }
+
+    /// Whether `name` falls under the domain filter and outside
+    /// `EXCLUDE_DOMAINS`; see `matches_domain_filter`.  The regex filters
+    /// apply as well.
+    pub fn in_domain_filter(&self, name: &str) -> bool {
+        matches_domain_filter(name, &self.domain_filter_list())
+            && !is_excluded(name, &self.exclude_domains_list())
+            && self.matches_domain_regex(name)
+    }
+
+    /// The zone `AUTO_CREATE_ZONES` would create for `name`: the longest
+    /// DOMAIN_FILTER entry at or above it.  `None` when auto-creation is
+    /// off or `name` is outside the domain filter.
+    pub fn auto_create_zone_for(&self, name: &str) -> Option<String> {
+        if !self.auto_create_zones || !self.in_domain_filter(name) {
+            return None;
+        }
+        let name = name_to_ascii(name.trim_end_matches('.'));
+        self.domain_filter_list()
+            .into_iter()
+            .map(|d| name_to_ascii(d.trim_end_matches('.')))
+            .filter(|d| name == *d || name.ends_with(&format!(".{d}")))
+            .max_by_key(String::len)
+    }
+
+    /// Whether `name` passes DOMAIN_FILTER_REGEX and escapes
+    /// DOMAIN_FILTER_REGEX_EXCLUSION.  Unset regexes don't filter.  The
+    /// name is matched without its trailing dot.
+    pub fn matches_domain_regex(&self, name: &str) -> bool {
+        let name = name.trim_end_matches('.');
+        self.domain_filter_regex.as_ref().is_none_or(|re| re.is_match(name))
+            && !self
+                .domain_filter_regex_exclusion
+                .as_ref()
+                .is_some_and(|re| re.is_match(name))
+    }
+}
+
+/// Whether `name` falls under any entry of `filter`.
+///
+/// An entry matches the name itself and anything below it on a label
+/// boundary, so `example.com` matches `sub.example.com` but not
+/// `notexample.com`.  Case, trailing dots and Unicode vs punycode labels
+/// are ignored on both sides.  An empty filter matches everything.
+pub fn matches_domain_filter(name: &str, filter: &[String]) -> bool {
+    if filter.is_empty() {
+        return true;
+    }
+    let name = name_to_ascii(name.trim_end_matches('.'));
+    filter.iter().any(|d| {
+        let d = name_to_ascii(d.trim_end_matches('.'));
+        name == d || name.ends_with(&format!(".{d}"))
+    })
+}
+
+/// Whether a DOMAIN_FILTER `entry` and a hosted `zone` overlap: the entry
+/// is the zone, lies inside it, or contains it.  Compared like
+/// `matches_domain_filter`.
+pub fn filter_overlaps_zone(entry: &str, zone: &str) -> bool {
+    let entry = name_to_ascii(entry.trim_end_matches('.'));
+    let zone = name_to_ascii(zone.trim_end_matches('.'));
+    entry == zone || entry.ends_with(&format!(".{zone}")) || zone.ends_with(&format!(".{entry}"))
+}
+
+/// Whether `name` falls under any entry of `exclude`.  Unlike the include
+/// side, an empty list excludes nothing.
+pub fn is_excluded(name: &str, exclude: &[String]) -> bool {
+    !exclude.is_empty() && matches_domain_filter(name, exclude)
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Parsing helpers
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Check PDNS_API_URL is an http(s) URL and bring it to the form API paths
+/// are appended to: a reverse-proxy path prefix is kept, while trailing
+/// slashes and a trailing `/api/v1` (a common copy-paste) are dropped.
+fn parse_api_url(raw: &str) -> Result<String, String> {
+    let url = reqwest::Url::parse(raw.trim()).map_err(|e| format!("'{raw}': {e}"))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("'{raw}' is not an http(s) URL"));
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(format!("'{raw}' must not have a query or fragment"));
+    }
+    let url = url.as_str().trim_end_matches('/');
+    let url = url.strip_suffix("/api/v1").unwrap_or(url);
+    Ok(url.trim_end_matches('/').to_string())
+}
+
+/// Compile an optional regex setting; empty means unset.
+fn parse_regex(pattern: &str, name: &str) -> anyhow::Result<Option<Regex>> {
+    if pattern.is_empty() {
+        return Ok(None);
+    }
+    Regex::new(pattern)
+        .map(Some)
+        .map_err(|e| anyhow::anyhow!("{name}: invalid regex: {e}"))
+}
+
+/// Parse a comma-separated list of HTTP status codes, e.g. "409, 423".
+fn parse_status_list(list: &str) -> anyhow::Result<Vec<u16>> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| match s.parse::<u16>() {
+            Ok(code) if (100..=599).contains(&code) => Ok(code),
+            _ => Err(anyhow::anyhow!("'{s}' is not an HTTP status code")),
+        })
+        .collect()
 }
 
 // ─────────────────────────────────────────────────────────────────────────────
@@ -109,12 +789,7 @@ impl Config {
 fn resolve_secret(file_path: &str, inline: &str, name: &str) -> anyhow::Result<String> {
     let path = Path::new(file_path);
     if path.exists() {
-        let raw = std::fs::read_to_string(path)
-            .map_err(|e| anyhow::anyhow!("reading secret file {file_path}: {e}"))?;
-        let value = raw.trim().to_string();
-        if value.is_empty() {
-            anyhow::bail!("secret file {file_path} is empty");
-        }
+        let value = read_secret_file(file_path)?;
         tracing::debug!("loaded {name} from file {file_path}");
         Ok(value)
     } else if !inline.is_empty() {
@@ -130,12 +805,44 @@ fn resolve_secret(file_path: &str, inline: &str, name: &str) -> anyhow::Result<S
     }
 }
 
+/// Read and trim a secret file, rejecting one that is empty.
+pub(crate) fn read_secret_file(file_path: &str) -> anyhow::Result<String> {
+    let raw = std::fs::read_to_string(file_path)
+        .map_err(|e| anyhow::anyhow!("reading secret file {file_path}: {e}"))?;
+    let value = raw.trim().to_string();
+    if value.is_empty() {
+        anyhow::bail!("secret file {file_path} is empty");
+    }
+    Ok(value)
+}
+
 // ─────────────────────────────────────────────────────────────────────────────
 // Defaults
 // ─────────────────────────────────────────────────────────────────────────────
 
-fn default_pdns_url()     -> String { "http://localhost:8081".into() }
-fn default_server_id()    -> String { "localhost".into() }
-fn default_ttl()          -> u32    { 300 }
-fn default_port()         -> u16    { 8888 }
-fn default_api_key_file() -> String { "/var/run/secrets/pdns/api-key".into() }
+fn default_pdns_url()             -> String { "http://localhost:8081".into() }
+fn default_server_id()            -> String { "localhost".into() }
+fn default_ttl()                  -> u32    { 300 }
+fn default_port()                 -> u16    { 8888 }
+fn default_bind_address()         -> String { "0.0.0.0".into() }
+fn default_api_key_file()         -> String { "/var/run/secrets/pdns/api-key".into() }
+fn default_api_key_reload_secs()  -> u64    { 60 }
+fn default_api_key_header()       -> String { "X-API-Key".into() }
+fn default_true()                 -> bool   { true }
+fn default_https_annotation()     -> String { "webhook/pdns-https-target".into() }
+fn default_preview_max_changes()  -> usize  { 1000 }
+fn default_request_timeout_secs() -> u64    { 30 }
+fn default_connect_timeout_secs() -> u64    { 5 }
+fn default_pool_max_idle()        -> usize  { usize::MAX }
+fn default_pool_idle_secs()       -> u64    { 90 }
+fn default_max_retries()          -> u32    { 3 }
+fn default_retry_base_ms()        -> u64    { 200 }
+fn default_cb_failure_threshold() -> u32    { 5 }
+fn default_cb_cooldown_seconds()  -> u64    { 30 }
+fn default_cb_window_seconds()    -> u64    { 60 }
+fn default_zone_cache_ttl()       -> u64    { 60 }
+fn default_fetch_concurrency()    -> usize  { 8 }
+fn default_zone_count_warn()      -> usize  { 1000 }
+fn default_handler_timeout_secs() -> u64    { 120 }
+fn default_max_body_bytes()       -> usize  { 8 * 1024 * 1024 }
+fn default_log_body_max_bytes()   -> usize  { 64 * 1024 }
@@ -1,4 +1,4 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 // ─────────────────────────────────────────────────────────────────────────────
 // external-dns webhook contract types
@@ -15,7 +15,9 @@ pub struct ProviderSpecific {
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Endpoint {
+    #[serde(deserialize_with = "lowercase")]
     pub dns_name: String,
+    #[serde(deserialize_with = "uppercase")]
     pub record_type: String,
     #[serde(default)]
     pub targets: Vec<String>,
@@ -29,6 +31,18 @@ pub struct Endpoint {
     pub set_identifier: String,
 }
 
+/// Some sources send `a` or `cname`; everything downstream matches on the
+/// uppercase type, so normalise it as the endpoint is read.
+fn uppercase<'de, D: Deserializer<'de>>(d: D) -> Result<String, D::Error> {
+    String::deserialize(d).map(|s| s.to_ascii_uppercase())
+}
+
+/// DNS names are case-insensitive and PowerDNS reports them lowercase, so
+/// names are compared, written and reported back in that form.
+fn lowercase<'de, D: Deserializer<'de>>(d: D) -> Result<String, D::Error> {
+    String::deserialize(d).map(|s| s.to_ascii_lowercase())
+}
+
 /// The payload sent by external-dns to POST /records.
 #[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -48,4 +62,47 @@ pub struct Changes {
 pub struct DomainFilter {
     pub include: Vec<String>,
     pub exclude: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+    #[serde(rename = "regexExclusion", skip_serializing_if = "Option::is_none")]
+    pub regex_exclusion: Option<String>,
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Internationalised names
+// ─────────────────────────────────────────────────────────────────────────────
+//
+// Sources may send Unicode names (`café.example.com`); PowerDNS only holds
+// the A-label form (`xn--caf-dma.example.com`).  Names are converted on the
+// way in and back on the way out, so external-dns sees what it sent.  Record
+// content is never converted.
+
+/// `name` in lowercase A-label form.  ASCII names are only lowercased, and a
+/// name IDNA rejects is passed on lowercased for PowerDNS to judge.
+pub fn name_to_ascii(name: &str) -> String {
+    if name.is_ascii() {
+        return name.to_ascii_lowercase();
+    }
+    idna::domain_to_ascii(name).unwrap_or_else(|_| name.to_lowercase())
+}
+
+/// `name` with its `xn--` labels decoded to Unicode; unchanged if it has
+/// none or they don't decode.
+pub fn name_to_unicode(name: &str) -> String {
+    if !name.split('.').any(|label| label.starts_with("xn--")) {
+        return name.to_string();
+    }
+    match idna::domain_to_unicode(name) {
+        (unicode, Ok(())) => unicode,
+        (_, Err(_)) => name.to_string(),
+    }
+}
+
+/// `name` with a trailing dot, added only if missing.
+pub fn ensure_fqdn(name: &str) -> String {
+    if name.ends_with('.') {
+        name.to_string()
+    } else {
+        format!("{name}.")
+    }
 }
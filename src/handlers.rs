@@ -1,12 +1,22 @@
+use std::{
+    any::Any,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
 use axum::{
-    extract::State,
+    extract::{Request, State},
     http::{HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
     response::{IntoResponse, Json, Response},
     Json as BodyJson,
 };
-use tracing::{debug, error, info, warn};
+use serde::Serialize;
+use tracing::{debug, error, field, info, info_span, warn, Instrument, Span};
 
 use crate::dns::{Changes, DomainFilter, Endpoint};
+use crate::config::{filter_overlaps_zone, ApplyMode, MergedTtlStrategy};
+use crate::pdns::{PdnsError, ZoneCache, ZonePatch, ZoneRef, ZoneStub};
+use crate::svc::{self, check_svc_params};
 use crate::AppState;
 
 // Content-Type required by the external-dns webhook spec
@@ -20,6 +30,7 @@ const WEBHOOK_CT: &str = "application/external.dns.webhook+json;version=1";
 //
 // external-dns places the key "webhook/pdns-https-target" into the endpoint's
 // providerSpecific array, which this webhook receives in /adjustendpoints.
+// The key is configurable with HTTPS_TARGET_ANNOTATION.
 //
 // The annotation value must be a valid HTTPS SvcParam string (RFC 9460):
 //   <priority> <target> [key=value ...]
@@ -28,7 +39,11 @@ const WEBHOOK_CT: &str = "application/external.dns.webhook+json;version=1";
 //   "1 . alpn=h2,h3"      – AliasMode, HTTP/2 + HTTP/3
 //   "1 . alpn=h2"         – AliasMode, HTTP/2 only
 //   "1 svc.example.com."  – ServiceMode with explicit target
-const HTTPS_TARGET_ANNOTATION: &str = "webhook/pdns-https-target";
+
+// SVCB records (RFC 9460 §2) use the same SvcParam format for non-HTTPS
+// services, e.g. provider-specific-webhook/pdns-svcb-target: "1 . alpn=h2".
+// Only annotated endpoints, and endpoints that are already SVCB, get one.
+const SVCB_TARGET_ANNOTATION: &str = "webhook/pdns-svcb-target";
 
 fn webhook_headers() -> HeaderMap {
     let mut h = HeaderMap::new();
@@ -36,18 +51,78 @@ fn webhook_headers() -> HeaderMap {
     h
 }
 
+// ── GET /version ──────────────────────────────────────────────────────────────
+
+/// Crate version this binary was built from.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Git commit this binary was built from, set by build.rs.
+pub const GIT_SHA: &str = env!("GIT_SHA");
+
+pub async fn version() -> impl IntoResponse {
+    Json(serde_json::json!({ "version": VERSION, "gitSha": GIT_SHA }))
+}
+
 // ── GET /healthz ──────────────────────────────────────────────────────────────
 
 pub async fn healthz() -> impl IntoResponse {
     (StatusCode::OK, Json(serde_json::json!({"status": "ok"})))
 }
 
+// ── GET /readyz ───────────────────────────────────────────────────────────────
+//
+// Ready only when PowerDNS answers; an open circuit breaker fails the check
+// without contacting PowerDNS.
+
+pub async fn readyz(State(state): State<AppState>) -> impl IntoResponse {
+    let result = state.provider.ping().await;
+    let circuit = state.provider.circuit_state();
+    match result {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "status": "ok", "circuit": circuit })),
+        ),
+        Err(e) => {
+            warn!("readiness check failed: {e:#}");
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(serde_json::json!({
+                    "status": "unavailable",
+                    "circuit": circuit,
+                    "error": format!("{e:#}"),
+                })),
+            )
+        }
+    }
+}
+
+// ── GET /status ───────────────────────────────────────────────────────────────
+//
+// When external-dns last synced successfully, for alerting on stalled syncs.
+// Every field is null until the first success since startup.
+
+pub async fn status(State(state): State<AppState>) -> impl IntoResponse {
+    let sync = *state.sync.read().unwrap();
+    let unix = |t: SystemTime| t.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let age = |t: SystemTime| t.elapsed().map_or(0, |d| d.as_secs());
+    Json(serde_json::json!({
+        "lastApplySuccess": sync.last_apply_success.map(unix),
+        "secondsSinceLastApplySuccess": sync.last_apply_success.map(age),
+        "endpointsLastRead": sync.endpoints_last_read,
+    }))
+}
+
 // ── GET / ─────────────────────────────────────────────────────────────────────
 
 pub async fn negotiate(State(state): State<AppState>) -> impl IntoResponse {
     let filter = DomainFilter {
         include: state.cfg.domain_filter_list(),
-        exclude: vec![],
+        exclude: state.cfg.exclude_domains_list(),
+        regex: state.cfg.domain_filter_regex.as_ref().map(|re| re.to_string()),
+        regex_exclusion: state
+            .cfg
+            .domain_filter_regex_exclusion
+            .as_ref()
+            .map(|re| re.to_string()),
     };
     (webhook_headers(), Json(filter))
 }
@@ -56,144 +131,655 @@ pub async fn negotiate(State(state): State<AppState>) -> impl IntoResponse {
 
 pub async fn get_records(State(state): State<AppState>) -> Response {
     let domain_filter = state.cfg.domain_filter_list();
-    match state.pdns.list_endpoints(&domain_filter).await {
-        Ok(eps) => {
+    let exclude = state.cfg.exclude_domains_list();
+    match state.provider.list_endpoints(&domain_filter, &exclude).await {
+        Ok(mut eps) => {
+            sort_endpoints(&mut eps);
+            state.sync.write().unwrap().endpoints_last_read = Some(eps.len());
             info!("GET /records → {} endpoint(s)", eps.len());
             (webhook_headers(), Json(eps)).into_response()
         }
         Err(e) => {
             error!("GET /records error: {e}");
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({"error": e.to_string()})),
-            )
-                .into_response()
+            error_response(e.status(), e.to_string())
         }
     }
 }
 
 // ── POST /records ─────────────────────────────────────────────────────────────
+//
+// Each call runs in an `apply_changes` span carrying the change counts, the
+// zones patched and the final status, closed by one INFO line, so a sync can
+// be followed without request body logging.
 
 pub async fn apply_changes(
     State(state): State<AppState>,
     BodyJson(changes): BodyJson<Changes>,
 ) -> Response {
+    let span = info_span!(
+        "apply_changes",
+        creates = field::Empty,
+        updates = field::Empty,
+        deletes = field::Empty,
+        zones = field::Empty,
+        status = field::Empty,
+    );
+    let response = apply(&state, changes).instrument(span.clone()).await;
+    span.record("status", response.status().as_u16());
+    if response.status().is_success() {
+        state.sync.write().unwrap().last_apply_success = Some(SystemTime::now());
+    }
+    span.in_scope(|| info!("POST /records → {}", response.status()));
+    response
+}
+
+async fn apply(state: &AppState, mut changes: Changes) -> Response {
     let ttl = state.cfg.default_ttl;
 
-    for ep in &changes.delete {
-        info!("DELETE {} {}", ep.record_type, ep.dns_name);
-        if let Err(e) = state.pdns.delete(ep).await {
-            error!("delete {}: {e}", ep.dns_name);
-            return error_response(502, e.to_string());
+    if let Err(msg) = prepare_changes(state, &mut changes) {
+        error!("POST /records: {msg}");
+        return error_response(422, msg);
+    }
+    let span = Span::current();
+    span.record("creates", changes.create.len());
+    span.record("updates", changes.update_new.len());
+    span.record("deletes", changes.delete.len());
+
+    let cache = ZoneCache::default();
+    if state.cfg.apply_mode == ApplyMode::Continue {
+        return apply_continue(state, &changes, &cache).await;
+    }
+
+    let plan = match state.provider.plan_changes(&changes, ttl, &cache).await {
+        Ok(plan) => plan,
+        Err(e) => {
+            error!("POST /records: {e}");
+            return error_response(e.status(), e.to_string());
+        }
+    };
+    record_zones(&plan);
+
+    let result = match state.cfg.apply_mode {
+        ApplyMode::BestEffort | ApplyMode::Continue => apply_best_effort(state, &plan).await,
+        ApplyMode::Atomic => apply_atomic(state, &plan).await,
+    };
+    match result {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err((code, msg)) => error_response(code, msg),
+    }
+}
+
+/// Apply `plan` zone by zone, stopping at the first failure.
+async fn apply_best_effort(state: &AppState, plan: &[ZonePatch]) -> Result<(), (u16, String)> {
+    for patch in plan {
+        if let Err(e) = state.provider.apply_patch(patch).await {
+            error!("PATCH {}: {e}", patch.zone);
+            return Err((e.status(), e.to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// Apply `plan` zone by zone; if a PATCH fails, put the zones already
+/// patched back the way they were.
+async fn apply_atomic(state: &AppState, plan: &[ZonePatch]) -> Result<(), (u16, String)> {
+    // Read every zone before writing any, so a read failure changes nothing.
+    let mut rollbacks = Vec::with_capacity(plan.len());
+    for patch in plan {
+        match state.provider.rollback_patch(patch).await {
+            Ok(rollback) => rollbacks.push(rollback),
+            Err(e) => {
+                error!("reading {} before atomic apply: {e}", patch.zone);
+                return Err((e.status(), e.to_string()));
+            }
         }
     }
 
-    for ep in &changes.update_old {
-        info!("UPDATE-OLD {} {}", ep.record_type, ep.dns_name);
-        if let Err(e) = state.pdns.delete(ep).await {
-            error!("update_old delete {}: {e}", ep.dns_name);
-            return error_response(502, e.to_string());
+    for (i, patch) in plan.iter().enumerate() {
+        let Err(e) = state.provider.apply_patch(patch).await else {
+            continue;
+        };
+        error!("PATCH {}: {e}; rolling back {i} zone(s)", patch.zone);
+
+        let mut failed = Vec::new();
+        for rollback in rollbacks[..i].iter().rev() {
+            if let Err(re) = state.provider.apply_patch(rollback).await {
+                error!("rollback of {} failed: {re}", rollback.zone);
+                failed.push(rollback.zone.clone());
+            }
         }
+
+        let outcome = if failed.is_empty() {
+            format!("rolled back {i} zone(s)")
+        } else {
+            format!("rollback FAILED for {}", failed.join(", "))
+        };
+        return Err((e.status(), format!("{e}; {outcome}")));
     }
+    Ok(())
+}
 
-    for ep in &changes.update_new {
-        info!("UPDATE-NEW {} {}", ep.record_type, ep.dns_name);
-        if let Err(e) = state.pdns.upsert(ep, ttl).await {
-            error!("update_new upsert {}: {e}", ep.dns_name);
-            return error_response(502, e.to_string());
+/// An endpoint `APPLY_MODE=continue` could not apply.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EndpointFailure {
+    dns_name: String,
+    record_type: String,
+    error: String,
+    #[serde(skip)]
+    status: u16,
+}
+
+impl EndpointFailure {
+    fn new(ep: &Endpoint, e: &PdnsError) -> Self {
+        Self {
+            dns_name: ep.dns_name.clone(),
+            record_type: ep.record_type.clone(),
+            error: e.to_string(),
+            status: e.status(),
         }
     }
+}
 
-    for ep in &changes.create {
-        info!("CREATE {} {}", ep.record_type, ep.dns_name);
-        if let Err(e) = state.pdns.upsert(ep, ttl).await {
-            error!("create {}: {e}", ep.dns_name);
-            return error_response(502, e.to_string());
+/// Apply everything that can be applied and report every endpoint that
+/// couldn't.  Endpoints are planned one at a time so a bad one only fails
+/// itself; the survivors are then planned and applied together.
+async fn apply_continue(state: &AppState, changes: &Changes, cache: &ZoneCache) -> Response {
+    let ttl = state.cfg.default_ttl;
+    let mut failures: Vec<EndpointFailure> = Vec::new();
+    let mut planned = Changes::default();
+    // (zone, endpoint) for everything that planned cleanly.  Keyed by server
+    // as well, since the same zone id can exist on several servers.
+    let mut zone_of: Vec<(ZoneRef, Endpoint)> = Vec::new();
+
+    type Bucket = fn(&mut Changes) -> &mut Vec<Endpoint>;
+    let buckets: [(Bucket, &Vec<Endpoint>); 4] = [
+        (|c| &mut c.delete, &changes.delete),
+        (|c| &mut c.update_old, &changes.update_old),
+        (|c| &mut c.update_new, &changes.update_new),
+        (|c| &mut c.create, &changes.create),
+    ];
+    for (bucket, endpoints) in buckets {
+        for ep in endpoints {
+            let mut single = Changes::default();
+            bucket(&mut single).push(ep.clone());
+            match state.provider.plan_changes(&single, ttl, cache).await {
+                Ok(plan) => {
+                    zone_of.extend(plan.iter().map(|p| (p.zone_ref(), ep.clone())));
+                    bucket(&mut planned).push(ep.clone());
+                }
+                Err(e) => {
+                    warn!("{} {}: {e}", ep.record_type, ep.dns_name);
+                    failures.push(EndpointFailure::new(ep, &e));
+                }
+            }
         }
     }
 
-    StatusCode::NO_CONTENT.into_response()
+    // Deleting the old half of an update whose new half failed would just
+    // remove the record.
+    planned.update_old.retain(|old| {
+        !failures
+            .iter()
+            .any(|f| f.dns_name == old.dns_name && f.record_type == old.record_type)
+    });
+    zone_of.retain(|(_, ep)| {
+        !failures
+            .iter()
+            .any(|f| f.dns_name == ep.dns_name && f.record_type == ep.record_type)
+    });
+
+    let mut applied = 0;
+    match state.provider.plan_changes(&planned, ttl, cache).await {
+        Ok(plan) => {
+            record_zones(&plan);
+            for patch in &plan {
+                match state.provider.apply_patch(patch).await {
+                    Ok(()) => applied += patch.rrsets.len(),
+                    Err(e) => {
+                        error!("PATCH {}: {e}", patch.zone);
+                        let zone = patch.zone_ref();
+                        for (_, ep) in zone_of.iter().filter(|(z, _)| *z == zone) {
+                            failures.push(EndpointFailure::new(ep, &e));
+                        }
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            error!("POST /records: {e}");
+            failures.extend(zone_of.iter().map(|(_, ep)| EndpointFailure::new(ep, &e)));
+        }
+    }
+
+    if failures.is_empty() {
+        return StatusCode::NO_CONTENT.into_response();
+    }
+
+    // One shared status if every failure agrees, otherwise a generic 502.
+    let status = failures[0].status;
+    let status = if failures.iter().all(|f| f.status == status) { status } else { 502 };
+    (
+        StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY),
+        Json(serde_json::json!({ "applied": applied, "failed": failures })),
+    )
+        .into_response()
+}
+
+// ── POST /records/preview ─────────────────────────────────────────────────────
+//
+// Not part of the external-dns webhook spec.  Accepts the same `Changes` body
+// as POST /records and returns the PATCHes that would be sent to PowerDNS,
+// grouped per zone, without applying anything.
+
+pub async fn preview_changes(
+    State(state): State<AppState>,
+    BodyJson(mut changes): BodyJson<Changes>,
+) -> Response {
+    if let Err(msg) = prepare_changes(&state, &mut changes) {
+        error!("preview: {msg}");
+        return error_response(422, msg);
+    }
+
+    let cache = ZoneCache::dry_run();
+    match state.provider.plan_changes(&changes, state.cfg.default_ttl, &cache).await {
+        Ok(plan) => {
+            let total: usize = plan.iter().map(|p| p.rrsets.len()).sum();
+            info!("POST /records/preview → {total} change(s) in {} zone(s)", plan.len());
+
+            let (plan, omitted) = truncate_plan(plan, state.cfg.preview_max_changes);
+            let mut body = serde_json::json!({ "zones": plan, "totalChanges": total });
+            if omitted > 0 {
+                body["omitted"] = format!("...{omitted} more changes omitted").into();
+            }
+            Json(body).into_response()
+        }
+        Err(e) => {
+            error!("preview: {e}");
+            error_response(e.status(), e.to_string())
+        }
+    }
+}
+
+/// Keep at most `max` RRset changes, in plan order, dropping zones left
+/// empty.  Returns the kept plan and how many changes were cut.
+fn truncate_plan(plan: Vec<ZonePatch>, max: usize) -> (Vec<ZonePatch>, usize) {
+    let mut remaining = max;
+    let mut omitted = 0;
+    let mut kept = Vec::with_capacity(plan.len());
+
+    for mut patch in plan {
+        if patch.rrsets.len() > remaining {
+            omitted += patch.rrsets.len() - remaining;
+            patch.rrsets.truncate(remaining);
+        }
+        remaining -= patch.rrsets.len();
+        if !patch.rrsets.is_empty() {
+            kept.push(patch);
+        }
+    }
+    (kept, omitted)
 }
 
 // ── POST /adjustendpoints ─────────────────────────────────────────────────────
 
+/// Return `endpoints` followed by the HTTPS/SVCB endpoints derived from
+/// them.  Settings that change the derivation are read from `state.cfg`;
+/// with the defaults this is the fixed behaviour the handler always had.
 pub async fn adjust_endpoints(
-    BodyJson(mut endpoints): BodyJson<Vec<Endpoint>>,
+    State(state): State<AppState>,
+    BodyJson(endpoints): BodyJson<Vec<Endpoint>>,
 ) -> impl IntoResponse {
+    if endpoints.is_empty() {
+        debug!("POST /adjustendpoints with no endpoints; nothing to adjust");
+        return (webhook_headers(), Json(endpoints));
+    }
+
+    let svc_types = [
+        SvcType {
+            record_type: "HTTPS",
+            annotation: &state.cfg.https_target_annotation,
+            default_target: state.cfg.default_https_target.as_deref(),
+            mirror_all: true,
+        },
+        SvcType {
+            record_type: "SVCB",
+            annotation: SVCB_TARGET_ANNOTATION,
+            default_target: None,
+            mirror_all: false,
+        },
+    ];
+
     let mut augmented_endpoints: Vec<Endpoint> = vec![];
-    for ep in &mut endpoints {
-        augmented_endpoints.push(ep.clone());
-
-        let mut https_endpoint = ep.clone();
-        if let Some(annotation_value) = find_provider_specific(&https_endpoint, HTTPS_TARGET_ANNOTATION) {
-            let target = validate_https_target(&annotation_value, &https_endpoint.dns_name);
-            info!(
-                "HTTPS {} → target from annotation '{}': {}",
-                https_endpoint.dns_name, HTTPS_TARGET_ANNOTATION, target
-            );
-            https_endpoint.targets = vec![target];
-        }
-
-        if https_endpoint.targets.is_empty() {
-            warn!(
-                "HTTPS {} has no targets and no '{}' annotation; skipping",
-                https_endpoint.dns_name, HTTPS_TARGET_ANNOTATION
-            );
-            continue;
+    for ep in &endpoints {
+        let derived: Vec<Endpoint> = svc_types.iter().filter_map(|svc| svc_endpoint(ep, svc)).collect();
+        // A derived endpoint of the endpoint's own type replaces it: returning
+        // both would have external-dns plan two changes to one RRset.
+        if !derived.iter().any(|d| d.record_type == ep.record_type) {
+            augmented_endpoints.push(ep.clone());
         }
+        augmented_endpoints.extend(derived);
+    }
 
-        https_endpoint.record_type = "HTTPS".into();
+    (webhook_headers(), Json(augmented_endpoints))
+}
 
-        https_endpoint.targets = https_endpoint
-            .targets
-            .iter()
-            .map(|t| normalise_https_target(t, &https_endpoint.dns_name))
-            .collect();
-        augmented_endpoints.push(https_endpoint.clone());
+/// A SvcParam-formatted record type `/adjustendpoints` derives endpoints for.
+struct SvcType<'a> {
+    record_type: &'static str,
+    annotation: &'a str,
+    /// Target for an endpoint of this type with neither annotation nor
+    /// targets.
+    default_target: Option<&'a str>,
+    /// Derive a record from every endpoint, not only annotated ones or ones
+    /// already of this type.  HTTPS has always done this.
+    mirror_all: bool,
+}
 
-        debug!("HTTPS {} → normalised targets: {:?}", https_endpoint.dns_name, https_endpoint.targets);
+/// The `svc.record_type` endpoint derived from `ep`: targets come from the
+/// type's annotation if present and valid, otherwise from `ep`, otherwise
+/// from the type's default, and are normalised to carry a SvcPriority.
+fn svc_endpoint(ep: &Endpoint, svc: &SvcType<'_>) -> Option<Endpoint> {
+    let rtype = svc.record_type;
+    // A bad annotation only loses its override; the endpoint is then handled
+    // as if it had none.
+    let annotation = find_provider_specific(ep, svc.annotation).and_then(|value| {
+        let target = normalise_svc_target(&value, rtype, &ep.dns_name, "annotation");
+        match check_svc_params(&target) {
+            Ok(()) => Some(target),
+            Err(reason) => {
+                warn!(
+                    "{rtype} {} annotation '{}' ignored: {reason}",
+                    ep.dns_name, svc.annotation
+                );
+                None
+            }
+        }
+    });
+    if annotation.is_none() && !svc.mirror_all && ep.record_type != rtype {
+        return None;
     }
 
-    (webhook_headers(), Json(augmented_endpoints))
+    let mut svc_ep = ep.clone();
+    if let Some(target) = annotation {
+        info!(
+            "{rtype} {} → target from annotation '{}': {}",
+            svc_ep.dns_name, svc.annotation, target
+        );
+        svc_ep.targets = vec![target];
+    }
+
+    if svc_ep.targets.is_empty() && ep.record_type == rtype {
+        if let Some(default) = svc.default_target {
+            debug!("{rtype} {} → default target: {default}", svc_ep.dns_name);
+            svc_ep.targets = vec![default.to_string()];
+        }
+    }
+
+    if svc_ep.targets.is_empty() {
+        warn!(
+            "{rtype} {} has no targets and no '{}' annotation; skipping",
+            svc_ep.dns_name, svc.annotation
+        );
+        return None;
+    }
+
+    svc_ep.record_type = rtype.into();
+    svc_ep.targets = svc_ep
+        .targets
+        .iter()
+        .map(|t| normalise_svc_target(t, rtype, &svc_ep.dns_name, "target"))
+        .collect();
+
+    debug!("{rtype} {} → normalised targets: {:?}", svc_ep.dns_name, svc_ep.targets);
+    Some(svc_ep)
+}
+
+// ── GET /zones (debug) ────────────────────────────────────────────────────────
+//
+// Not part of the external-dns webhook API; only routed with
+// ENABLE_DEBUG_ENDPOINTS=true.  Answers "why isn't my zone managed?" by
+// listing every zone PowerDNS hosts with how the filters treat it.
+
+/// A zone as PowerDNS lists it, plus whether the filters let us manage it.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DebugZone {
+    #[serde(flatten)]
+    stub: ZoneStub,
+    matches_domain_filter: bool,
+    account_allowed: bool,
+}
+
+pub async fn debug_zones(State(state): State<AppState>) -> Response {
+    let stubs = match state.provider.list_zones().await {
+        Ok(stubs) => stubs,
+        Err(e) => {
+            error!("GET /zones: {e}");
+            return error_response(e.status(), e.to_string());
+        }
+    };
+
+    let filter = state.cfg.domain_filter_list();
+    let accounts = state.cfg.account_filter_list();
+    let zones: Vec<DebugZone> = stubs
+        .into_iter()
+        .map(|stub| DebugZone {
+            matches_domain_filter: filter.is_empty()
+                || filter.iter().any(|f| filter_overlaps_zone(f, &stub.name)),
+            account_allowed: accounts.is_empty() || accounts.contains(&stub.account),
+            stub,
+        })
+        .collect();
+    Json(serde_json::json!({ "domainFilter": filter, "zones": zones })).into_response()
+}
+
+/// Answer 504 when a webhook request outlives HANDLER_TIMEOUT_SECS, so a
+/// PowerDNS that hangs mid-sync can't hold the connection open forever.
+/// Changes already PATCHed stay applied; the next sync reconciles the rest.
+pub async fn handler_timeout(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let secs = state.cfg.handler_timeout_secs;
+    if secs == 0 {
+        return next.run(req).await;
+    }
+
+    let route = format!("{} {}", req.method(), req.uri().path());
+    match tokio::time::timeout(Duration::from_secs(secs), next.run(req)).await {
+        Ok(resp) => resp,
+        Err(_) => {
+            error!("{route}: no response within HANDLER_TIMEOUT_SECS ({secs}s)");
+            error_response(504, format!("{route} timed out after {secs}s"))
+        }
+    }
+}
+
+/// Turn a handler panic caught by `CatchPanicLayer` into a logged 500, so
+/// external-dns gets a status it retries instead of a connection reset.
+pub fn panic_response(panic: Box<dyn Any + Send + 'static>) -> Response {
+    let msg = panic
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| panic.downcast_ref::<&str>().copied())
+        .unwrap_or("<non-string panic payload>");
+    error!("handler panicked: {msg}");
+    error_response(500, "internal error".into())
 }
 
 // ── helpers ───────────────────────────────────────────────────────────────────
 
-fn find_provider_specific(ep: &Endpoint, key: &str) -> Option<String> {
-    ep.provider_specific
-        .iter()
-        .find(|p| p.name == key)
-        .map(|p| p.value.clone())
+/// Record the zones `plan` touches on the current `apply_changes` span.
+fn record_zones(plan: &[ZonePatch]) {
+    let zones: Vec<&str> = plan.iter().map(|p| p.zone.as_str()).collect();
+    Span::current().record("zones", zones.join(","));
+}
+
+/// Order endpoints by name then type, and each endpoint's targets, so GET
+/// /records answers the same way however PowerDNS ordered things.
+fn sort_endpoints(eps: &mut [Endpoint]) {
+    for ep in eps.iter_mut() {
+        ep.targets.sort();
+    }
+    eps.sort_by(|a, b| (&a.dns_name, &a.record_type).cmp(&(&b.dns_name, &b.record_type)));
+}
+
+/// Validate and reshape a `Changes` payload before it is planned or applied.
+fn prepare_changes(state: &AppState, changes: &mut Changes) -> Result<(), String> {
+    scope_to_domain_filter(state, changes)?;
+
+    if state.cfg.merge_same_name_endpoints {
+        let strategy = state.cfg.merged_ttl_strategy;
+        changes.update_new = merge_same_name(std::mem::take(&mut changes.update_new), strategy);
+        changes.create = merge_same_name(std::mem::take(&mut changes.create), strategy);
+    }
+    check_cname_conflicts(changes)
 }
 
-fn validate_https_target(value: &str, dns_name: &str) -> String {
-    let trimmed = value.trim();
-    let parts: Vec<&str> = trimmed.splitn(3, ' ').collect();
+/// Reject a batch that would leave a CNAME and another type at the same
+/// name, which DNS forbids (RFC 1034 §3.6.2).  Only the records the batch
+/// writes are compared, not what PowerDNS already holds.
+fn check_cname_conflicts(changes: &Changes) -> Result<(), String> {
+    let written: Vec<&Endpoint> = changes.update_new.iter().chain(&changes.create).collect();
+    let key = |ep: &Endpoint| ep.dns_name.trim_end_matches('.').to_ascii_lowercase();
 
-    match parts.as_slice() {
-        [priority, _target, ..] if priority.parse::<u16>().is_ok() => trimmed.to_string(),
-        [_target, ..] => {
-            warn!("HTTPS {} annotation missing SvcPriority; prepending '1'", dns_name);
-            format!("1 {trimmed}")
+    let mut conflicts: Vec<String> = Vec::new();
+    for cname in written.iter().filter(|ep| ep.record_type == "CNAME") {
+        let name = key(cname);
+        let mut others: Vec<&str> = written
+            .iter()
+            .filter(|ep| ep.record_type != "CNAME" && key(ep) == name)
+            .map(|ep| ep.record_type.as_str())
+            .collect();
+        others.sort_unstable();
+        others.dedup();
+        if !others.is_empty() && !conflicts.iter().any(|c| c.starts_with(&format!("{name}:"))) {
+            conflicts.push(format!("{name}: CNAME alongside {}", others.join(", ")));
         }
-        _ => {
-            warn!(
-                "HTTPS {} annotation '{}' has unexpected format; wrapping as '1 . {}'",
-                dns_name, value, trimmed
-            );
-            format!("1 . {trimmed}")
+    }
+
+    if conflicts.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("CNAME cannot coexist with other records: {}", conflicts.join("; ")))
+    }
+}
+
+/// Collapse endpoints that share a (name, type) into one endpoint carrying
+/// the union of their targets.
+///
+/// external-dns splits e.g. weighted records into several endpoints for the
+/// same name; since every upsert REPLACEs the whole RRset, applying them one
+/// by one would leave only the last endpoint's targets.  The first endpoint
+/// of each group keeps its position and its other fields; conflicting TTLs
+/// are resolved by `MERGED_TTL_STRATEGY`.
+fn merge_same_name(endpoints: Vec<Endpoint>, strategy: MergedTtlStrategy) -> Vec<Endpoint> {
+    let mut merged: Vec<Endpoint> = Vec::with_capacity(endpoints.len());
+
+    for ep in endpoints {
+        let existing = merged.iter_mut().find(|m| {
+            m.record_type == ep.record_type
+                && m.dns_name.trim_end_matches('.') == ep.dns_name.trim_end_matches('.')
+        });
+        match existing {
+            Some(m) => {
+                debug!(
+                    "merging {} {} targets {:?} into existing endpoint",
+                    ep.record_type, ep.dns_name, ep.targets
+                );
+                m.record_ttl = merge_ttl(m, ep.record_ttl, strategy);
+                for t in ep.targets {
+                    if !m.targets.contains(&t) {
+                        m.targets.push(t);
+                    }
+                }
+            }
+            None => merged.push(ep),
         }
     }
+
+    merged
+}
+
+/// Guard against writing outside the negotiated domain filter.
+///
+/// external-dns should never send such changes, but a misconfigured instance
+/// can.  By default the offending endpoints are dropped with a warning and
+/// the rest are applied; with `SKIP_OUT_OF_FILTER_CHANGES=false` the whole
+/// request is rejected.
+fn scope_to_domain_filter(state: &AppState, changes: &mut Changes) -> Result<(), String> {
+    let cfg = &state.cfg;
+    let mut rejected: Vec<String> = Vec::new();
+
+    for bucket in [
+        &mut changes.create,
+        &mut changes.update_old,
+        &mut changes.update_new,
+        &mut changes.delete,
+    ] {
+        bucket.retain(|ep| {
+            if cfg.in_domain_filter(&ep.dns_name) {
+                return true;
+            }
+            if cfg.skip_out_of_filter_changes {
+                warn!(
+                    "{} {} is outside the domain filter; skipping",
+                    ep.record_type, ep.dns_name
+                );
+            } else {
+                rejected.push(ep.dns_name.clone());
+            }
+            false
+        });
+    }
+
+    if rejected.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "refusing changes outside the domain filter: {}",
+            rejected.join(", ")
+        ))
+    }
+}
+
+/// Resolve the TTL of merged endpoint `m` when another endpoint with `ttl`
+/// joins it.  A TTL of 0 means "unset" and is always overridden.
+fn merge_ttl(m: &Endpoint, ttl: u32, strategy: MergedTtlStrategy) -> u32 {
+    let current = m.record_ttl;
+    if ttl == 0 || ttl == current {
+        return current;
+    }
+    if current == 0 {
+        return ttl;
+    }
+
+    let chosen = match strategy {
+        MergedTtlStrategy::First => current,
+        MergedTtlStrategy::Min => current.min(ttl),
+        MergedTtlStrategy::Max => current.max(ttl),
+    };
+    warn!(
+        "{} {} merged endpoints have conflicting TTLs {current} and {ttl}; using {chosen} ({:?})",
+        m.record_type, m.dns_name, strategy
+    );
+    chosen
+}
+
+fn find_provider_specific(ep: &Endpoint, key: &str) -> Option<String> {
+    ep.provider_specific
+        .iter()
+        .find(|p| p.name == key)
+        .map(|p| p.value.clone())
 }
 
-fn normalise_https_target(target: &str, dns_name: &str) -> String {
-    let t = target.trim();
-    let first = t.split_whitespace().next().unwrap_or("");
-    if first.parse::<u16>().is_ok() {
-        return t.to_string();
+/// `target` normalised by `svc::normalise_svc_target`, warning when it had
+/// to be given a SvcPriority.  `source` says where it came from for the log.
+fn normalise_svc_target(target: &str, rtype: &str, dns_name: &str, source: &str) -> String {
+    let normalised = svc::normalise_svc_target(target);
+    let first = target.split_whitespace().next().unwrap_or_default();
+    if first.parse::<u16>().is_err() {
+        warn!("{rtype} {dns_name} {source} '{target}' has no SvcPriority; using '{normalised}'");
     }
-    warn!("HTTPS {} target '{}' missing SvcPriority; prepending '1'", dns_name, target);
-    format!("1 {t}")
+    normalised
 }
 
 fn error_response(code: u16, msg: String) -> Response {
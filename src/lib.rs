@@ -1,14 +1,47 @@
+pub mod auth;
+pub mod breaker;
 pub mod config;
 pub mod dns;
 pub mod handlers;
 pub mod pdns;
+pub mod provider;
+pub mod router;
+pub mod svc;
+
+pub use router::app;
 
 // AppState lives here so every module can reach it via `crate::AppState`
 // without a separate module import.
-use crate::{config::Config, pdns::PdnsClient};
+use std::{
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
+
+use crate::{config::Config, provider::DnsProvider};
 
+/// Cloned by axum for every request, so everything in it is behind an `Arc`.
 #[derive(Clone)]
 pub struct AppState {
-    pub cfg: Config,
-    pub pdns: PdnsClient,
+    /// Shared with the provider, so the resolved secrets exist once.
+    pub cfg: Arc<Config>,
+    /// The DNS backend; `PdnsClient` in production.
+    pub provider: Arc<dyn DnsProvider>,
+    /// Outcome of recent syncs, reported by `GET /status`.
+    pub sync: Arc<RwLock<SyncStatus>>,
+}
+
+/// What the last syncs achieved, so a stalled external-dns can be alerted on
+/// while the process itself looks healthy.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SyncStatus {
+    /// When `POST /records` last fully succeeded.
+    pub last_apply_success: Option<SystemTime>,
+    /// Endpoints returned by the last successful `GET /records`.
+    pub endpoints_last_read: Option<usize>,
+}
+
+impl AppState {
+    pub fn new(cfg: impl Into<Arc<Config>>, provider: impl DnsProvider + 'static) -> Self {
+        Self { cfg: cfg.into(), provider: Arc::new(provider), sync: Arc::default() }
+    }
 }
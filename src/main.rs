@@ -9,54 +9,39 @@ use pdns_webhook::{
     AppState,
 };
 
-use std::net::SocketAddr;
-
-use axum::{
-    body::Body,
-    extract::Request,
-    middleware::{self, Next},
-    response::Response,
-    routing::{get, post},
-    Router,
-};
-use http_body_util::BodyExt;
-use tower_http::trace::TraceLayer;
-use tracing::{debug, info};
+use std::{net::SocketAddr, sync::Arc};
+
+use anyhow::Context;
+use axum_server::tls_rustls::RustlsConfig;
+use tracing::{debug, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 // ─────────────────────────────────────────────────────────────────────────────
-// Request body logging middleware
+// Graceful shutdown
 // ─────────────────────────────────────────────────────────────────────────────
 
-async fn log_request_body(req: Request, next: Next) -> Response {
-    let (parts, body) = req.into_parts();
+/// Resolve on SIGTERM (Kubernetes rollout) or SIGINT (Ctrl-C).
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install SIGINT handler");
+    };
 
-    let bytes = match body.collect().await {
-        Ok(collected) => collected.to_bytes(),
-        Err(e) => {
-            tracing::error!("failed to read request body: {e}");
-            return next.run(Request::from_parts(parts, Body::empty())).await;
-        }
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
     };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
 
-    if tracing::enabled!(tracing::Level::DEBUG) {
-        let body_str = std::str::from_utf8(&bytes)
-            .map(|s| {
-                serde_json::from_str::<serde_json::Value>(s)
-                    .map(|v| serde_json::to_string_pretty(&v).unwrap_or_else(|_| s.to_string()))
-                    .unwrap_or_else(|_| s.to_string())
-            })
-            .unwrap_or_else(|_| format!("<{} binary bytes>", bytes.len()));
-
-        debug!(
-            method = %parts.method,
-            path   = %parts.uri.path(),
-            body   = %body_str,
-            "← request body"
-        );
+    tokio::select! {
+        _ = ctrl_c => info!("SIGINT received; shutting down after in-flight requests"),
+        _ = terminate => info!("SIGTERM received; shutting down after in-flight requests"),
     }
-
-    next.run(Request::from_parts(parts, Body::from(bytes))).await
 }
 
 // ─────────────────────────────────────────────────────────────────────────────
@@ -65,6 +50,14 @@ async fn log_request_body(req: Request, next: Next) -> Response {
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    // A .env in the working directory is for local development; variables
+    // already set in the process environment win over it.
+    let dotenv = match dotenvy::dotenv() {
+        Ok(path) => Some(path),
+        Err(e) if e.not_found() => None,
+        Err(e) => anyhow::bail!("loading .env: {e}"),
+    };
+
     let filter = match EnvFilter::try_from_default_env() {
         Ok(f) => {
             eprintln!(
@@ -74,26 +67,41 @@ async fn main() -> anyhow::Result<()> {
             f
         }
         Err(e) => {
-            let default = "pdns_webhook=debug,tower_http=debug";
+            let default = "pdns_webhook=debug,server=info,tower_http=debug";
             eprintln!("[tracing] RUST_LOG not set or invalid ({e}), defaulting to: {default}");
             EnvFilter::new(default)
         }
     };
 
-    tracing_subscriber::registry()
-        .with(filter)
-        .with(
-            tracing_subscriber::fmt::layer()
-                .with_target(true)
-                .with_file(true)
-                .with_line_number(true)
-                .with_thread_ids(false)
-                .with_ansi(true),
-        )
-        .init();
+    // Read straight from the environment, like RUST_LOG, because logging has
+    // to be up before `Config::from_env` so its warnings are seen.
+    let log_format = std::env::var("LOG_FORMAT").unwrap_or_default();
+    let json = match log_format.trim().to_ascii_lowercase().as_str() {
+        "" | "pretty" => false,
+        "json"        => true,
+        other         => anyhow::bail!("LOG_FORMAT: '{other}' is not 'json' or 'pretty'"),
+    };
+
+    let fmt = tracing_subscriber::fmt::layer()
+        .with_target(true)
+        .with_file(true)
+        .with_line_number(true)
+        .with_thread_ids(false);
+    let registry = tracing_subscriber::registry().with(filter);
+    if json {
+        registry.with(fmt.json().with_ansi(false)).init();
+    } else {
+        registry.with(fmt.with_ansi(true)).init();
+    }
+    if let Some(path) = dotenv {
+        debug!("loaded environment from {}", path.display());
+    }
+
+    info!("pdns-webhook {} ({})", handlers::VERSION, handlers::GIT_SHA);
 
     let cfg = Config::from_env()?;
     let port = cfg.port;
+    let bind_address = cfg.bind_address;
 
     info!("PowerDNS API : {}", cfg.pdns_api_url);
     info!("Server ID    : {}", cfg.pdns_server_id);
@@ -102,25 +110,67 @@ async fn main() -> anyhow::Result<()> {
         if cfg.domain_filter.is_empty() { "(all zones)" } else { &cfg.domain_filter }
     );
     info!("Default TTL  : {}s", cfg.default_ttl);
+    if cfg.dry_run {
+        warn!("DRY_RUN is on: changes are logged, never sent to PowerDNS");
+    }
+
+    // Load the certificate up front so a bad pair fails startup, not the
+    // first handshake.
+    let tls = match cfg.tls_files() {
+        Some((cert, key)) => Some(
+            RustlsConfig::from_pem_file(cert, key)
+                .await
+                .with_context(|| format!("loading TLS certificate {cert} and key {key}"))?,
+        ),
+        None => None,
+    };
 
+    let cfg = Arc::new(cfg);
     let pdns = PdnsClient::new(cfg.clone())?;
-    let state = AppState { cfg, pdns };
-
-    let app = Router::new()
-        .route("/",                get(handlers::negotiate))
-        .route("/healthz",         get(handlers::healthz))
-        .route("/records",         get(handlers::get_records))
-        .route("/records",         post(handlers::apply_changes))
-        .route("/adjustendpoints", post(handlers::adjust_endpoints))
-        .layer(middleware::from_fn(log_request_body))
-        .layer(TraceLayer::new_for_http())
-        .with_state(state);
-
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    info!("Listening on {addr}");
-
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    if cfg.startup_pdns_check {
+        pdns.check_server().await.context("startup check (STARTUP_PDNS_CHECK=false skips it)")?;
+        info!("PowerDNS server {} reachable", cfg.pdns_server_id);
+    }
+    if cfg.validate_domain_filter {
+        pdns.check_domain_filter().await.context("VALIDATE_DOMAIN_FILTER")?;
+    }
+    pdns.spawn_api_key_reload();
+    let state = AppState::new(cfg, pdns);
+
+    if state.cfg.webhook_auth_token.is_some() {
+        info!("Webhook auth : bearer token required");
+    }
+
+    let app = pdns_webhook::app(state);
+
+    let addr = SocketAddr::new(bind_address, port);
+
+    match tls {
+        Some(tls) => {
+            info!("Listening on {addr} (TLS)");
+            let handle = axum_server::Handle::new();
+            tokio::spawn({
+                let handle = handle.clone();
+                async move {
+                    shutdown_signal().await;
+                    handle.graceful_shutdown(None);
+                }
+            });
+            axum_server::bind_rustls(addr, tls)
+                .handle(handle)
+                .serve(app.into_make_service())
+                .await?;
+        }
+        None => {
+            info!("Listening on {addr}");
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown_signal())
+                .await?;
+        }
+    }
+
+    info!("Shutdown complete");
 
     Ok(())
 }
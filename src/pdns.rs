@@ -1,26 +1,80 @@
-use anyhow::{anyhow, bail, Context, Result};
-use reqwest::Client;
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::{Ipv4Addr, Ipv6Addr},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context};
+use futures::{stream, StreamExt};
+use reqwest::{header::HeaderValue, Certificate, Client, Identity, RequestBuilder, Response};
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error, info};
+use tokio::sync::{Mutex, OwnedMutexGuard, RwLock};
+use tracing::{debug, error, info, warn};
 
-use crate::{config::Config, dns::Endpoint};
+use crate::{
+    breaker::{BreakerState, CircuitBreaker, CircuitOpen},
+    config::{filter_overlaps_zone, is_excluded, matches_domain_filter, read_secret_file, Config},
+    dns::{ensure_fqdn, name_to_ascii, name_to_unicode, Changes, Endpoint, ProviderSpecific},
+    svc::{check_svc_target, normalise_svc_target},
+};
 
 // ─────────────────────────────────────────────────────────────────────────────
 // PowerDNS API shapes (partial – only what we need)
 // ─────────────────────────────────────────────────────────────────────────────
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ZoneStub {
     pub id: String,
     pub name: String,
+    #[serde(default)]
+    pub account: String,
+    /// Native, Master, Slave, Producer or Consumer.
+    #[serde(default)]
+    pub kind: String,
+    /// The PDNS_SERVER_ID the zone was listed from; not sent by PowerDNS.
+    #[serde(default)]
+    pub server: String,
+}
+
+impl ZoneStub {
+    pub fn zone_ref(&self) -> ZoneRef {
+        ZoneRef { server: self.server.clone(), id: self.id.clone() }
+    }
+
+    /// Whether the zone is a read-only replica PowerDNS won't PATCH.
+    pub fn is_secondary(&self) -> bool {
+        self.kind.eq_ignore_ascii_case("slave") || self.kind.eq_ignore_ascii_case("consumer")
+    }
+}
+
+/// A zone on one of the configured PowerDNS servers.  Displays as the zone
+/// id alone, which is what the logs have always shown.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ZoneRef {
+    pub server: String,
+    pub id: String,
+}
+
+impl std::fmt::Display for ZoneRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.id)
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Zone {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub account: String,
     pub rrsets: Vec<RrSet>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct RrSet {
     pub name: String,
     #[serde(rename = "type")]
@@ -29,16 +83,186 @@ pub struct RrSet {
     #[serde(default)]
     pub records: Vec<Record>,
     /// Used in PATCH requests; omit on read
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub changetype: Option<String>,
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub comments: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub comments: Vec<Comment>,
+}
+
+/// Serialized by hand because whether `comments` is sent depends on
+/// `changetype`: PowerDNS keeps an RRset's existing comments when a REPLACE
+/// has no comments key, so a REPLACE always carries one, even if empty, or
+/// a meta comment could never be cleared; `plan_changes` merges other
+/// accounts' comments into it.  Otherwise empty comments are left out.
+impl Serialize for RrSet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let replace = self.changetype.as_deref() == Some("REPLACE");
+        let mut s = serializer.serialize_struct("RrSet", 6)?;
+        s.serialize_field("name", &self.name)?;
+        s.serialize_field("type", &self.rrtype)?;
+        s.serialize_field("ttl", &self.ttl)?;
+        s.serialize_field("records", &self.records)?;
+        match &self.changetype {
+            Some(changetype) => s.serialize_field("changetype", changetype)?,
+            None => s.skip_field("changetype")?,
+        }
+        if replace || !self.comments.is_empty() {
+            s.serialize_field("comments", &self.comments)?;
+        } else {
+            s.skip_field("comments")?;
+        }
+        s.end()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Comment {
+    pub content: String,
+    #[serde(default)]
+    pub account: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Metadata {
+    #[serde(default)]
+    pub metadata: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Record {
     pub content: String,
     pub disabled: bool,
+    /// Ask PowerDNS to maintain the matching PTR record (A/AAAA only).
+    #[serde(rename = "set-ptr", default, skip_serializing_if = "std::ops::Not::not")]
+    pub set_ptr: bool,
+}
+
+/// Record types whose RRset may hold only one record.
+const SINGLE_VALUED_TYPES: &[&str] = &["CNAME", "ALIAS"];
+
+/// A record that can't be written as requested, reported before anything is
+/// sent to PowerDNS.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid {record_type} record {name}: {reason}")]
+pub struct InvalidRecord {
+    pub name: String,
+    pub record_type: String,
+    pub reason: String,
+}
+
+/// Why a `PdnsClient` call failed, so handlers can answer with a fitting
+/// status instead of a blanket 502.
+#[derive(Debug, thiserror::Error)]
+pub enum PdnsError {
+    /// No zone this webhook may manage covers the name.  A record or
+    /// configuration problem, not a PowerDNS one, hence 422 rather than 5xx.
+    #[error("no PowerDNS zone found for {name}{}", .hint.as_ref().map(|h| format!("; {h}")).unwrap_or_default())]
+    ZoneNotFound { name: String, hint: Option<String> },
+    /// The zone is excluded via `ZONE_SUSPEND_METADATA_KIND`.
+    #[error("zone {zone} is suspended from external-dns management (metadata {kind}); refusing to write {name}")]
+    ZoneSuspended { zone: String, kind: String, name: String },
+    /// PowerDNS answered with an error status.
+    #[error("PowerDNS {what} → {status}: {body}")]
+    Upstream { what: String, status: u16, body: String },
+    /// PowerDNS could not be reached.
+    #[error("{what}: {source}")]
+    Connection { what: String, source: reqwest::Error },
+    /// PowerDNS could not be reached with `PDNS_HTTP2=true`, typically
+    /// because the endpoint only speaks HTTP/1.1.
+    #[error("{what}: {source} (PDNS_HTTP2 is set; does the endpoint accept HTTP/2?)")]
+    Http2 { what: String, source: reqwest::Error },
+    /// PowerDNS did not answer within `PDNS_REQUEST_TIMEOUT_SECS`.
+    #[error("{what}: PowerDNS at {url} timed out")]
+    Timeout { what: String, url: String },
+    /// PowerDNS answered 2xx with a body that isn't what the API documents.
+    #[error("PowerDNS {what}: unexpected response: {reason}")]
+    InvalidResponse { what: String, reason: String },
+    #[error(transparent)]
+    InvalidRecord(#[from] InvalidRecord),
+    #[error(transparent)]
+    CircuitOpen(#[from] CircuitOpen),
+    /// The provider does not support the operation at all.
+    #[error("{0}")]
+    Unsupported(String),
+}
+
+impl PdnsError {
+    /// The HTTP status a handler should answer with.
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::ZoneSuspended { .. }   => 409,
+            Self::ZoneNotFound { .. }
+            | Self::InvalidRecord(_)     => 422,
+            Self::Unsupported(_)         => 501,
+            Self::Upstream { .. }
+            | Self::Connection { .. }
+            | Self::Http2 { .. }
+            | Self::InvalidResponse { .. } => 502,
+            Self::CircuitOpen(_)         => 503,
+            Self::Timeout { .. }         => 504,
+        }
+    }
+}
+
+type Result<T, E = PdnsError> = std::result::Result<T, E>;
+
+/// Why an RRset is being written, from external-dns's point of view.
+///
+/// Both are sent to PowerDNS as `REPLACE`; the intent only affects logging
+/// and, with `AUDIT_INTENT_COMMENTS=true`, the comment attached to the RRset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeIntent {
+    Create,
+    Update,
+}
+
+impl ChangeIntent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Update => "update",
+        }
+    }
+}
+
+/// The zone list fetched while handling a single request.
+///
+/// Created per `apply_changes` and passed down the call chain so the zone
+/// list is read from PowerDNS at most once per request, however many
+/// endpoints it resolves.  It is never shared across requests, so it cannot
+/// go stale beyond the request's own writes.
+#[derive(Default)]
+pub struct ZoneCache {
+    zones: tokio::sync::Mutex<Option<Arc<Vec<ZoneStub>>>>,
+    /// Zone contents fetched so far.
+    contents: tokio::sync::Mutex<HashMap<ZoneRef, Arc<Zone>>>,
+    /// Whether each zone checked so far is suspended.
+    suspended: tokio::sync::Mutex<HashMap<ZoneRef, bool>>,
+    dry_run: bool,
+}
+
+impl ZoneCache {
+    /// A cache for planning without writing: zones `AUTO_CREATE_ZONES` would
+    /// create are reported by name instead of created.
+    pub fn dry_run() -> Self {
+        Self { dry_run: true, ..Self::default() }
+    }
+}
+
+/// The RRset changes destined for a single zone, in the order they would be
+/// sent to PowerDNS.
+#[derive(Debug, Clone, Serialize)]
+pub struct ZonePatch {
+    pub server: String,
+    pub zone: String,
+    pub rrsets: Vec<RrSet>,
+}
+
+impl ZonePatch {
+    pub fn zone_ref(&self) -> ZoneRef {
+        ZoneRef { server: self.server.clone(), id: self.zone.clone() }
+    }
 }
 
 // ─────────────────────────────────────────────────────────────────────────────
@@ -48,173 +272,915 @@ pub struct Record {
 #[derive(Clone)]
 pub struct PdnsClient {
     http: Client,
-    cfg: Config,
+    cfg: Arc<Config>,
+    breaker: Arc<CircuitBreaker>,
+    zones: Arc<RwLock<Option<CachedZones>>>,
+    /// Current API key; starts as `cfg.pdns_api_key` and is swapped by
+    /// `reload_api_key` when the key file changes.
+    api_key: Arc<std::sync::RwLock<ApiKey>>,
+    /// Set once the ZONE_COUNT_WARN warning has been logged.
+    zone_count_warned: Arc<AtomicBool>,
+    /// One lock per zone, held around each PATCH and the rectify/notify
+    /// after it, so overlapping requests write a zone one at a time.
+    zone_locks: Arc<Mutex<HashMap<ZoneRef, Arc<Mutex<()>>>>>,
+}
+
+/// The API key in the forms it is used in, built once per key so each
+/// request only clones two handles.
+#[derive(Clone)]
+struct ApiKey {
+    /// For redacting logs.
+    secret: Arc<str>,
+    /// Sent with every request; marked sensitive so it is never logged.
+    header: HeaderValue,
+}
+
+impl ApiKey {
+    fn new(key: &str) -> anyhow::Result<Self> {
+        let mut header = HeaderValue::from_str(key).context("PowerDNS API key is not a valid header value")?;
+        header.set_sensitive(true);
+        Ok(Self { secret: key.into(), header })
+    }
+}
+
+/// The zone list as last fetched from PowerDNS, shared by all requests and
+/// refreshed once it is older than `ZONE_CACHE_TTL`.
+struct CachedZones {
+    fetched_at: Instant,
+    zones: Arc<Vec<ZoneStub>>,
 }
 
 impl PdnsClient {
-    pub fn new(cfg: Config) -> Result<Self> {
-        let http = Client::builder()
-            .build()
-            .context("building reqwest client")?;
-        Ok(Self { http, cfg })
+    pub fn new(cfg: impl Into<Arc<Config>>) -> anyhow::Result<Self> {
+        let cfg = cfg.into();
+        let mut builder = Client::builder()
+            .timeout(Duration::from_secs(cfg.pdns_request_timeout_secs))
+            .connect_timeout(Duration::from_secs(cfg.pdns_connect_timeout_secs))
+            .pool_max_idle_per_host(cfg.pdns_pool_max_idle_per_host)
+            .pool_idle_timeout(
+                Some(Duration::from_secs(cfg.pdns_pool_idle_timeout_secs)).filter(|d| !d.is_zero()),
+            );
+        if cfg.pdns_http2 {
+            builder = builder.http2_prior_knowledge();
+        }
+
+        if !cfg.pdns_client_cert_file.is_empty() {
+            let cert = read_pem(&cfg.pdns_client_cert_file)?;
+            let key = read_pem(&cfg.pdns_client_key_file)?;
+            let identity = Identity::from_pkcs8_pem(&cert, &key).with_context(|| {
+                format!(
+                    "loading PowerDNS client certificate {} and key {}",
+                    cfg.pdns_client_cert_file, cfg.pdns_client_key_file
+                )
+            })?;
+            builder = builder.identity(identity);
+        }
+        if !cfg.pdns_ca_file.is_empty() {
+            let ca = Certificate::from_pem(&read_pem(&cfg.pdns_ca_file)?)
+                .with_context(|| format!("loading PowerDNS CA {}", cfg.pdns_ca_file))?;
+            builder = builder.add_root_certificate(ca);
+        }
+
+        let http = builder.build().context("building reqwest client")?;
+        let breaker = Arc::new(CircuitBreaker::new(
+            cfg.pdns_cb_failure_threshold,
+            Duration::from_secs(cfg.pdns_cb_cooldown_seconds),
+            Duration::from_secs(cfg.pdns_cb_window_seconds),
+        ));
+        let api_key = Arc::new(std::sync::RwLock::new(ApiKey::new(&cfg.pdns_api_key)?));
+        Ok(Self {
+            http,
+            cfg,
+            breaker,
+            zones: Arc::default(),
+            api_key,
+            zone_count_warned: Arc::default(),
+            zone_locks: Arc::default(),
+        })
+    }
+
+    /// Current state of the circuit breaker guarding PowerDNS calls.
+    pub fn circuit_state(&self) -> BreakerState {
+        self.breaker.state()
+    }
+
+    fn base(&self, server: &str) -> String {
+        self.cfg.server_url(server)
+    }
+
+    /// The server zones are created on: the first in PDNS_SERVER_ID.
+    fn primary_server(&self) -> String {
+        self.cfg.server_ids().swap_remove(0)
+    }
+
+    fn api_key(&self) -> ApiKey {
+        self.api_key.read().unwrap().clone()
     }
 
-    fn base(&self) -> String {
-        format!(
-            "{}/api/v1/servers/{}",
-            self.cfg.pdns_api_url.trim_end_matches('/'),
-            self.cfg.pdns_server_id
-        )
+    /// `text` with every occurrence of the current API key replaced by
+    /// `***`, for anything that may end up in a log.
+    pub fn redact(&self, text: &str) -> String {
+        redact_secret(text, &self.api_key().secret)
     }
 
-    fn api_key(&self) -> &str {
-        &self.cfg.pdns_api_key
+    /// An `Upstream` error for a non-2xx `resp`, with its body redacted in
+    /// case PowerDNS or a proxy echoed the key back.
+    async fn upstream_error(&self, what: impl Into<String>, resp: Response) -> PdnsError {
+        let status = resp.status().as_u16();
+        let body = self.redact(&resp.text().await.unwrap_or_default());
+        PdnsError::Upstream { what: what.into(), status, body }
+    }
+
+    /// Re-read `PDNS_API_KEY_FILE` and start using its contents if they
+    /// changed, returning whether they did.  A key given inline is static,
+    /// so this is a no-op for it.
+    pub fn reload_api_key(&self) -> anyhow::Result<bool> {
+        let Some(file) = &self.cfg.pdns_api_key_file else {
+            return Ok(false);
+        };
+        let key = read_secret_file(file)?;
+        let mut current = self.api_key.write().unwrap();
+        if *current.secret == *key {
+            return Ok(false);
+        }
+        *current = ApiKey::new(&key)?;
+        info!("PowerDNS API key reloaded from {file}");
+        Ok(true)
+    }
+
+    /// Call `reload_api_key` every `PDNS_API_KEY_RELOAD_SECS` in the
+    /// background, so a rotated Kubernetes secret is picked up without a
+    /// restart.  A file that can't be read keeps the current key.
+    pub fn spawn_api_key_reload(&self) {
+        let secs = self.cfg.pdns_api_key_reload_secs;
+        if self.cfg.pdns_api_key_file.is_none() || secs == 0 {
+            return;
+        }
+        let client = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(secs));
+            interval.tick().await; // the first tick completes immediately
+            loop {
+                interval.tick().await;
+                if let Err(e) = client.reload_api_key() {
+                    warn!("keeping the current PowerDNS API key: {e:#}");
+                }
+            }
+        });
+    }
+
+    /// Send a request to PowerDNS through the circuit breaker.
+    ///
+    /// Connection errors and 5xx responses count as failures; any other
+    /// response, including a 4xx, shows PowerDNS is up and resets the count.
+    async fn send(&self, req: RequestBuilder, what: &str) -> Result<Response> {
+        self.breaker.check()?;
+        match req.header(&self.cfg.pdns_api_key_header, self.api_key().header).send().await {
+            Ok(resp) => {
+                if resp.status().is_server_error() {
+                    self.breaker.record_failure();
+                } else {
+                    self.breaker.record_success();
+                }
+                Ok(resp)
+            }
+            Err(e) if e.is_timeout() => {
+                self.breaker.record_failure();
+                let url = e.url().map(|u| u.to_string()).unwrap_or_default();
+                Err(PdnsError::Timeout { what: what.to_string(), url })
+            }
+            Err(source) => {
+                self.breaker.record_failure();
+                let what = what.to_string();
+                if self.cfg.pdns_http2 && is_h2_error(&source) {
+                    Err(PdnsError::Http2 { what, source })
+                } else {
+                    Err(PdnsError::Connection { what, source })
+                }
+            }
+        }
+    }
+
+    /// `send`, retrying transient failures – connection errors and the
+    /// statuses `Config::is_retryable_status` accepts – up to
+    /// `PDNS_MAX_RETRIES` times with jittered exponential backoff.  Other
+    /// 4xx responses come back at once, as does an open circuit breaker.
+    async fn send_retrying(&self, req: RequestBuilder, what: &str) -> Result<Response> {
+        let max_retries = self.cfg.pdns_max_retries;
+        let mut attempt = 0;
+        loop {
+            // Bodies built with `.json()` are buffered, so this only fails
+            // for streaming bodies, which we never send.
+            let Some(this_try) = req.try_clone() else {
+                return self.send(req, what).await;
+            };
+
+            let reason = match self.send(this_try, what).await {
+                Ok(resp) if self.cfg.is_retryable_status(resp.status().as_u16()) => {
+                    if attempt >= max_retries {
+                        return Ok(resp);
+                    }
+                    format!("HTTP {}", resp.status())
+                }
+                Ok(resp) => return Ok(resp),
+                Err(e @ PdnsError::CircuitOpen(_)) => return Err(e),
+                Err(e) if attempt >= max_retries => return Err(e),
+                Err(e) => format!("{e:#}"),
+            };
+
+            attempt += 1;
+            let delay = backoff_delay(self.cfg.pdns_retry_base_ms, attempt);
+            warn!(
+                "{what}: {reason}; retry {attempt}/{max_retries} in {}ms",
+                delay.as_millis()
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Whether a zone owned by `account` may be managed under
+    /// `PDNS_ACCOUNT_FILTER`.
+    fn account_allowed(&self, account: &str) -> bool {
+        let accounts = self.cfg.account_filter_list();
+        accounts.is_empty() || accounts.iter().any(|a| a == account)
+    }
+
+    /// Whether `zone` is a kind this webhook writes to: anything but a
+    /// Slave or Consumer zone, unless `MANAGE_SLAVE_ZONES` is set.
+    fn kind_allowed(&self, zone: &ZoneStub) -> bool {
+        self.cfg.manage_slave_zones || !zone.is_secondary()
+    }
+
+    /// The intent to record on written RRsets, if audit comments are enabled.
+    fn audit(&self, intent: ChangeIntent) -> Option<ChangeIntent> {
+        self.cfg.audit_intent_comments.then_some(intent)
+    }
+
+    /// Cheap connectivity check for readiness: fetch each server object and
+    /// give up after a short timeout.
+    pub async fn ping(&self) -> Result<()> {
+        const PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+        for server in self.cfg.server_ids() {
+            let resp = self
+                .send(self.http.get(self.base(&server)).timeout(PING_TIMEOUT), "GET /servers/:id")
+                .await?;
+            if !resp.status().is_success() {
+                return Err(self.upstream_error("GET /servers/:id", resp).await);
+            }
+        }
+        Ok(())
+    }
+
+    /// Startup check: fetch each server object, with retries, and turn the
+    /// failures a misconfiguration produces into errors naming the setting
+    /// to fix.
+    pub async fn check_server(&self) -> anyhow::Result<()> {
+        for id in self.cfg.server_ids() {
+            self.check_one_server(&id).await?;
+        }
+        Ok(())
+    }
+
+    async fn check_one_server(&self, id: &str) -> anyhow::Result<()> {
+        let url = &self.cfg.pdns_api_url;
+        let resp = self
+            .send_retrying(self.http.get(self.base(id)), "GET /servers/:id")
+            .await
+            .with_context(|| format!("cannot reach PowerDNS at {url}; check PDNS_API_URL"))?;
+
+        match resp.status().as_u16() {
+            200..=299 => Ok(()),
+            401 | 403 => bail!(
+                "PowerDNS at {url} rejected the API key ({}); check PDNS_API_KEY_FILE / PDNS_API_KEY",
+                resp.status()
+            ),
+            404 => bail!(
+                "PowerDNS at {url} has no server '{id}' (404); check PDNS_SERVER_ID and that \
+                 PDNS_API_URL is the API base URL"
+            ),
+            _ => {
+                let status = resp.status();
+                let body = resp.text().await.unwrap_or_default();
+                bail!("PowerDNS GET server {id} → {status}: {body}")
+            }
+        }
+    }
+
+    /// Startup check for `VALIDATE_DOMAIN_FILTER`: fail, listing them, if
+    /// any DOMAIN_FILTER entries neither fall inside nor contain a zone this
+    /// webhook may manage.  An empty filter manages every zone and passes.
+    pub async fn check_domain_filter(&self) -> anyhow::Result<()> {
+        let filter = self.cfg.domain_filter_list();
+        if filter.is_empty() {
+            return Ok(());
+        }
+        let zones: Vec<ZoneStub> = self
+            .list_zones()
+            .await?
+            .into_iter()
+            .filter(|z| self.account_allowed(&z.account) && self.kind_allowed(z))
+            .collect();
+
+        let unmatched: Vec<String> = filter
+            .into_iter()
+            .filter(|f| !zones.iter().any(|z| filter_overlaps_zone(f, &z.name)))
+            .collect();
+        if !unmatched.is_empty() {
+            bail!("DOMAIN_FILTER entries match no PowerDNS zone: {}", unmatched.join(", "));
+        }
+        Ok(())
     }
 
     // ── zones ────────────────────────────────────────────────────────────────
 
-    /// List all zones (stub objects only).
+    /// List all zones (stub objects only) on every configured server.
+    ///
+    /// PowerDNS has no pagination for the zone list: each server answers
+    /// with every zone at once.  Past `ZONE_COUNT_WARN` zones a warning says
+    /// that GET /records, which reads each zone, is getting expensive.
     pub async fn list_zones(&self) -> Result<Vec<ZoneStub>> {
-        let url = format!("{}/zones", self.base());
+        let mut all = Vec::new();
+        for server in self.cfg.server_ids() {
+            let url = format!("{}/zones", self.base(&server));
+            let resp = self.send_retrying(self.http.get(&url), "GET /zones").await?;
+
+            if !resp.status().is_success() {
+                return Err(self.upstream_error("GET /zones", resp).await);
+            }
+            let zones: Vec<ZoneStub> = json(resp, "GET /zones").await?;
+            all.extend(zones.into_iter().map(|z| ZoneStub { server: server.clone(), ..z }));
+        }
+
+        let limit = self.cfg.zone_count_warn;
+        if limit > 0 && all.len() > limit && !self.zone_count_warned.swap(true, Ordering::Relaxed) {
+            warn!(
+                "PowerDNS lists {} zones (ZONE_COUNT_WARN={limit}); GET /records fetches every \
+                 one, so consider DOMAIN_FILTER or PDNS_ACCOUNT_FILTER to narrow it",
+                all.len()
+            );
+        }
+        Ok(all)
+    }
+
+    /// The zone list, served from the shared cache while it is younger than
+    /// `ZONE_CACHE_TTL` and fetched again otherwise.
+    pub async fn zones(&self) -> Result<Arc<Vec<ZoneStub>>> {
+        let ttl = Duration::from_secs(self.cfg.zone_cache_ttl);
+        if let Some(cached) = self.zones.read().await.as_ref() {
+            if cached.fetched_at.elapsed() < ttl {
+                return Ok(cached.zones.clone());
+            }
+        }
+
+        let zones = Arc::new(self.list_zones().await?);
+        *self.zones.write().await = Some(CachedZones {
+            fetched_at: Instant::now(),
+            zones: zones.clone(),
+        });
+        Ok(zones)
+    }
+
+    /// Drop the cached zone list so the next lookup fetches it again.
+    pub async fn invalidate_zones(&self) {
+        self.zones.write().await.take();
+    }
+
+    /// Fetch a zone with all its RRsets.
+    pub async fn get_zone(&self, zone: &ZoneRef) -> Result<Zone> {
+        let url = format!("{}/zones/{}", self.base(&zone.server), zone.id);
+        let resp = self.send_retrying(self.http.get(&url), "GET /zones/:id").await?;
+
+        if !resp.status().is_success() {
+            return Err(self.upstream_error(format!("GET zone {zone}"), resp).await);
+        }
+        json(resp, "GET /zones/:id").await
+    }
+
+    /// Create a zone on the first configured server and return the id
+    /// PowerDNS assigned to it.
+    ///
+    /// The id is usually the zone name, but PowerDNS escapes some characters
+    /// and proxies may rewrite it, so it is taken from the created zone object
+    /// (or failing that the `Location` header) rather than assumed.
+    pub async fn create_zone(&self, name: &str, kind: &str) -> Result<String> {
+        let url = format!("{}/zones", self.base(&self.primary_server()));
+        let name = canonical_name(name);
+        let mut payload = serde_json::json!({ "name": name, "kind": kind, "nameservers": [] });
+        // Give the zone an account PDNS_ACCOUNT_FILTER lets us manage.
+        if let Some(account) = self.cfg.account_filter_list().first() {
+            payload["account"] = account.as_str().into();
+        }
+
         let resp = self
-            .http
-            .get(&url)
-            .header("X-API-Key", self.api_key())
-            .send()
-            .await
-            .context("GET /zones")?;
+            .send(self.http.post(&url).json(&payload), "POST /zones")
+            .await?;
 
         if !resp.status().is_success() {
-            let status = resp.status();
-            let body = resp.text().await.unwrap_or_default();
-            bail!("PowerDNS GET /zones {}: {}", status, body);
+            return Err(self.upstream_error(format!("POST /zones {name}"), resp).await);
         }
-        Ok(resp.json().await?)
+
+        let location = resp
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|l| l.trim_end_matches('/').rsplit('/').next())
+            .map(String::from);
+
+        let id = match resp.json::<ZoneStub>().await {
+            Ok(created) => created.id,
+            Err(e) => match location {
+                Some(id) => id,
+                None => {
+                    return Err(PdnsError::InvalidResponse {
+                        what: "POST /zones".into(),
+                        reason: format!("created zone {name} but returned no id: {e}"),
+                    })
+                }
+            },
+        };
+
+        info!("created zone {name} (id {id})");
+        self.invalidate_zones().await;
+        Ok(id)
     }
 
-    /// Fetch a zone with all its RRsets.
-    pub async fn get_zone(&self, zone_id: &str) -> Result<Zone> {
-        let url = format!("{}/zones/{}", self.base(), zone_id);
+    /// Whether the zone carries the `ZONE_SUSPEND_METADATA_KIND` metadata with
+    /// a truthy value ("1", "true" or "yes").  Always false when unconfigured.
+    pub async fn zone_suspended(&self, zone: &ZoneRef) -> Result<bool> {
+        let kind = &self.cfg.zone_suspend_metadata_kind;
+        if kind.is_empty() {
+            return Ok(false);
+        }
+
+        let url = format!("{}/zones/{}/metadata/{}", self.base(&zone.server), zone.id, kind);
         let resp = self
-            .http
-            .get(&url)
-            .header("X-API-Key", self.api_key())
-            .send()
-            .await
-            .context("GET /zones/:id")?;
+            .send(self.http.get(&url), "GET /zones/:id/metadata/:kind")
+            .await?;
 
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
         if !resp.status().is_success() {
-            bail!("PowerDNS GET zone {} → {}", zone_id, resp.status());
+            let what = format!("GET metadata {kind} for {zone}");
+            return Err(self.upstream_error(what, resp).await);
         }
-        Ok(resp.json().await?)
+
+        let meta: Metadata = json(resp, "GET /zones/:id/metadata/:kind").await?;
+        Ok(meta
+            .metadata
+            .iter()
+            .any(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes")))
     }
 
-    /// Walk up the DNS tree to find the best matching zone for `fqdn`.
-    pub async fn zone_for(&self, fqdn: &str) -> Result<String> {
-        let labels: Vec<&str> = fqdn.trim_end_matches('.').split('.').collect();
-        for i in 1..labels.len() {
-            let candidate = format!("{}.", labels[i..].join("."));
-            let url = format!("{}/zones/{}", self.base(), candidate);
-            let resp = self
-                .http
-                .get(&url)
-                .header("X-API-Key", self.api_key())
-                .send()
-                .await?;
-            if resp.status().is_success() {
-                debug!("zone_for({fqdn}) → {candidate}");
-                return Ok(candidate);
+    /// The zone list through the request-scoped `cache`, taken from the
+    /// shared zone cache on first use so one request sees a single snapshot.
+    async fn cached_zones(&self, cache: &ZoneCache) -> Result<Arc<Vec<ZoneStub>>> {
+        let mut zones = cache.zones.lock().await;
+        if let Some(zones) = zones.as_ref() {
+            return Ok(zones.clone());
+        }
+        let fetched = self.zones().await?;
+        *zones = Some(fetched.clone());
+        Ok(fetched)
+    }
+
+    /// A zone's contents through the request-scoped `cache`, fetched at most
+    /// once per request.
+    async fn cached_zone(&self, zone_ref: &ZoneRef, cache: &ZoneCache) -> Result<Arc<Zone>> {
+        let mut contents = cache.contents.lock().await;
+        if let Some(zone) = contents.get(zone_ref) {
+            return Ok(zone.clone());
+        }
+        let zone = Arc::new(self.get_zone(zone_ref).await?);
+        contents.insert(zone_ref.clone(), zone.clone());
+        Ok(zone)
+    }
+
+    /// `zone_suspended` through the request-scoped `cache`, so the metadata
+    /// is read at most once per zone per request.
+    async fn cached_suspended(&self, zone_ref: &ZoneRef, cache: &ZoneCache) -> Result<bool> {
+        let mut suspended = cache.suspended.lock().await;
+        if let Some(&s) = suspended.get(zone_ref) {
+            return Ok(s);
+        }
+        let s = self.zone_suspended(zone_ref).await?;
+        suspended.insert(zone_ref.clone(), s);
+        Ok(s)
+    }
+
+    /// With `PRESERVE_EXISTING_TTL`, the TTL the RRset for `ep` already has
+    /// in `zone`, if `ep` carries none of its own.
+    async fn preserved_ttl(
+        &self,
+        ep: &Endpoint,
+        zone: &ZoneRef,
+        cache: &ZoneCache,
+    ) -> Result<Option<u32>> {
+        if !self.cfg.preserve_existing_ttl || ep.record_ttl > 0 {
+            return Ok(None);
+        }
+        let name = canonical_name(&ep.dns_name);
+        let existing = self.cached_zone(zone, cache).await?;
+        Ok(existing
+            .rrsets
+            .iter()
+            .find(|r| r.rrtype == ep.record_type && r.name.eq_ignore_ascii_case(&name))
+            .map(|r| r.ttl))
+    }
+
+    /// Whether `zone` holds an RRset with `rrset`'s name and type, so a DELETE
+    /// of an RRset that's already gone can be skipped.
+    async fn exists(&self, rrset: &RrSet, zone: &ZoneRef, cache: &ZoneCache) -> Result<bool> {
+        let existing = self.cached_zone(zone, cache).await?;
+        Ok(find_rrset(&existing, rrset).is_some())
+    }
+
+    /// Whether `zone` already holds `rrset` exactly: same TTL, records and
+    /// webhook-owned comments, in any order.  `rrset` is built by
+    /// `build_rrset`, so its content is normalised the way PowerDNS stores
+    /// it.  Other accounts' comments are carried over by
+    /// `keep_foreign_comments` and never make an RRset differ.
+    async fn unchanged(&self, rrset: &RrSet, zone: &ZoneRef, cache: &ZoneCache) -> Result<bool> {
+        let existing = self.cached_zone(zone, cache).await?;
+        let Some(current) = find_rrset(&existing, rrset) else {
+            return Ok(false);
+        };
+
+        let records = |r: &RrSet| {
+            let mut records: Vec<(String, bool)> =
+                r.records.iter().map(|rec| (rec.content.clone(), rec.disabled)).collect();
+            records.sort();
+            records
+        };
+        let comments = |r: &RrSet| {
+            let mut comments: Vec<(String, String)> = r
+                .comments
+                .iter()
+                .filter(|c| webhook_owned(c))
+                .map(|c| (c.content.clone(), c.account.clone()))
+                .collect();
+            comments.sort();
+            comments
+        };
+        Ok(current.ttl == rrset.ttl
+            && records(current) == records(rrset)
+            && comments(current) == comments(rrset))
+    }
+
+    /// Carry the records an operator disabled in PowerDNS into `rrset`, so a
+    /// write from external-dns, which never sees them, neither enables nor
+    /// drops them.  A DELETE of an RRset holding disabled records becomes a
+    /// REPLACE with just those.
+    async fn keep_disabled(&self, rrset: &mut RrSet, zone: &ZoneRef, cache: &ZoneCache) -> Result<()> {
+        let existing = self.cached_zone(zone, cache).await?;
+        let Some(current) = find_rrset(&existing, rrset) else {
+            return Ok(());
+        };
+        let disabled: Vec<&Record> = current.records.iter().filter(|r| r.disabled).collect();
+        if disabled.is_empty() {
+            return Ok(());
+        }
+
+        if rrset.changetype.as_deref() == Some("DELETE") {
+            debug!("{} {}: keeping {} disabled record(s)", rrset.rrtype, rrset.name, disabled.len());
+            rrset.changetype = Some("REPLACE".into());
+            rrset.ttl = current.ttl;
+            rrset.comments = current.comments.clone();
+        }
+        let single = SINGLE_VALUED_TYPES.contains(&rrset.rrtype.as_str());
+        for record in disabled {
+            if let Some(r) = rrset.records.iter_mut().find(|r| r.content == record.content) {
+                r.disabled = true;
+            } else if !single || rrset.records.is_empty() {
+                // A CNAME can't hold a second record, even a disabled one.
+                rrset.records.push(record.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Carry the comments other accounts left on the RRset into `rrset`, so
+    /// a REPLACE, which always sends the full comment list, only rewrites
+    /// the webhook's own audit and meta comments.
+    async fn keep_foreign_comments(&self, rrset: &mut RrSet, zone: &ZoneRef, cache: &ZoneCache) -> Result<()> {
+        let existing = self.cached_zone(zone, cache).await?;
+        let Some(current) = find_rrset(&existing, rrset) else {
+            return Ok(());
+        };
+        rrset.comments.extend(current.comments.iter().filter(|c| !webhook_owned(c)).cloned());
+        Ok(())
+    }
+
+    /// Find the zone for `fqdn`: the longest hosted zone name that equals it
+    /// or is a suffix of it on a label boundary.  Every zone on every server
+    /// is considered, so the answer doesn't depend on the order PowerDNS
+    /// lists them in; a tie goes to the server listed first.
+    pub async fn zone_for(&self, fqdn: &str, cache: &ZoneCache) -> Result<ZoneRef> {
+        let name = canonical_name(fqdn);
+        let zones = self.cached_zones(cache).await?;
+
+        let mut best: Option<(&ZoneStub, usize)> = None;
+        for zone in zones.iter() {
+            let zone_name = canonical_name(&zone.name);
+            // The name itself counts, so apex records land in their own zone.
+            if name != zone_name && !name.ends_with(&format!(".{zone_name}")) {
+                continue;
+            }
+            if best.is_none_or(|(_, len)| zone_name.len() > len) {
+                best = Some((zone, zone_name.len()));
             }
         }
-        Err(anyhow!("no PowerDNS zone found for {fqdn}"))
+
+        let Some((zone, _)) = best else {
+            return self.auto_create_zone(fqdn, &zones, cache).await;
+        };
+        // Another account's zone and a secondary zone are refused rather than
+        // skipped: falling back to a parent zone would write records its
+        // delegation hides.
+        if !self.account_allowed(&zone.account) {
+            return Err(PdnsError::ZoneNotFound {
+                name: fqdn.to_string(),
+                hint: Some(format!(
+                    "{} belongs to account '{}', which PDNS_ACCOUNT_FILTER excludes",
+                    zone.name, zone.account
+                )),
+            });
+        }
+        if !self.kind_allowed(zone) {
+            return Err(PdnsError::ZoneNotFound {
+                name: fqdn.to_string(),
+                hint: Some(format!(
+                    "{} is a {} zone; MANAGE_SLAVE_ZONES=true writes to it",
+                    zone.name, zone.kind
+                )),
+            });
+        }
+        let zone_ref = zone.zone_ref();
+        if self.cached_suspended(&zone_ref, cache).await? {
+            return Err(PdnsError::ZoneSuspended {
+                zone: zone.name.clone(),
+                kind: self.cfg.zone_suspend_metadata_kind.clone(),
+                name: fqdn.to_string(),
+            });
+        }
+        debug!("zone_for({fqdn}) → {} on {}", zone.id, zone.server);
+        Ok(zone_ref)
+    }
+
+    /// Create the zone `AUTO_CREATE_ZONES` picks for `fqdn`, which `zone_for`
+    /// found no zone for.  Never creates a zone that exists under another
+    /// account, and only names it under a dry-run cache.
+    async fn auto_create_zone(
+        &self,
+        fqdn: &str,
+        zones: &[ZoneStub],
+        cache: &ZoneCache,
+    ) -> Result<ZoneRef> {
+        let Some(apex) = self.cfg.auto_create_zone_for(fqdn) else {
+            return Err(PdnsError::ZoneNotFound { name: fqdn.to_string(), hint: None });
+        };
+        let apex = canonical_name(&apex);
+        if zones.iter().any(|z| canonical_name(&z.name) == apex) {
+            return Err(PdnsError::ZoneNotFound {
+                name: fqdn.to_string(),
+                hint: Some(format!("{apex} exists under an account outside PDNS_ACCOUNT_FILTER")),
+            });
+        }
+        if cache.dry_run || self.cfg.dry_run {
+            if self.cfg.dry_run {
+                info!("DRY_RUN: would create zone {apex} for {fqdn}");
+            }
+            debug!("zone_for({fqdn}) → {apex} (would be created)");
+            let zone = ZoneRef { server: self.primary_server(), id: apex };
+            let empty = Zone { name: zone.id.clone(), account: String::new(), rrsets: vec![] };
+            cache.contents.lock().await.insert(zone.clone(), Arc::new(empty));
+            return Ok(zone);
+        }
+
+        let kind = self.cfg.auto_create_zone_kind.as_str();
+        info!("no zone for {fqdn}; creating {kind} zone {apex}");
+        let id = self.create_zone(&apex, kind).await?;
+        // Later endpoints in this request must see the new zone.
+        *cache.zones.lock().await = None;
+        Ok(ZoneRef { server: self.primary_server(), id })
     }
 
     // ── mutations ────────────────────────────────────────────────────────────
 
-    async fn patch_zone(&self, zone: &str, rrsets: Vec<RrSet>) -> Result<()> {
-        let url = format!("{}/zones/{}", self.base(), zone);
+    /// Take `zone`'s write lock, waiting for any other request mutating it.
+    /// Different zones never wait on each other.
+    async fn lock_zone(&self, zone: &ZoneRef) -> OwnedMutexGuard<()> {
+        let lock = self.zone_locks.lock().await.entry(zone.clone()).or_default().clone();
+        match lock.clone().try_lock_owned() {
+            Ok(guard) => guard,
+            Err(_) => {
+                debug!("{zone}: waiting for another request's changes to finish");
+                lock.lock_owned().await
+            }
+        }
+    }
+
+    async fn patch_zone(&self, zone: &ZoneRef, rrsets: &[RrSet]) -> Result<()> {
+        let url = format!("{}/zones/{}", self.base(&zone.server), zone.id);
         let payload = serde_json::json!({ "rrsets": rrsets });
+        if self.cfg.dry_run {
+            info!("DRY_RUN: would PATCH {url}: {payload}");
+            return Ok(());
+        }
 
         let resp = self
-            .http
-            .patch(&url)
-            .header("X-API-Key", self.api_key())
-            .json(&payload)
-            .send()
-            .await
-            .context("PATCH /zones/:id")?;
+            .send_retrying(self.http.patch(&url).json(&payload), "PATCH /zones/:id")
+            .await?;
 
-        let status = resp.status();
-        if !status.is_success() {
-            let body = resp.text().await.unwrap_or_default();
-            error!("PowerDNS PATCH {zone} [{status}]: {body}");
-            bail!("PowerDNS PATCH error {status}: {body}");
+        if !resp.status().is_success() {
+            let e = self.upstream_error(format!("PATCH {zone}"), resp).await;
+            error!("{e}");
+            return Err(e);
         }
+        self.invalidate_zones().await;
         Ok(())
     }
 
-    /// Create or replace an RRset for the given endpoint.
-    pub async fn upsert(&self, ep: &Endpoint, default_ttl: u32) -> Result<()> {
-        let zone = self.zone_for(&ep.dns_name).await?;
-        let rrset = build_rrset(ep, default_ttl, "REPLACE");
-        info!(
-            "UPSERT {rtype} {name} → {zone}",
-            rtype = ep.record_type,
-            name = ep.dns_name
-        );
-        self.patch_zone(&zone, vec![rrset]).await
-    }
-
-    /// Delete the RRset for the given endpoint.
-    pub async fn delete(&self, ep: &Endpoint) -> Result<()> {
-        let zone = self.zone_for(&ep.dns_name).await?;
-        let rrset = RrSet {
-            name: ensure_fqdn(&ep.dns_name),
-            rrtype: ep.record_type.clone(),
-            ttl: 0,
-            records: vec![],
-            changetype: Some("DELETE".into()),
-            comments: vec![],
-        };
-        info!(
-            "DELETE {rtype} {name} from {zone}",
-            rtype = ep.record_type,
-            name = ep.dns_name
-        );
-        self.patch_zone(&zone, vec![rrset]).await
+    /// Send all of one zone's planned RRset changes in a single PATCH, so
+    /// PowerDNS applies them atomically.
+    pub async fn apply_patch(&self, patch: &ZonePatch) -> Result<()> {
+        for rrset in &patch.rrsets {
+            info!(
+                "{changetype} {rtype} {name} in {zone}",
+                changetype = rrset.changetype.as_deref().unwrap_or_default(),
+                rtype = rrset.rrtype,
+                name = rrset.name,
+                zone = patch.zone
+            );
+        }
+        let zone = patch.zone_ref();
+        let _guard = self.lock_zone(&zone).await;
+        self.patch_zone(&zone, &patch.rrsets).await?;
+        self.after_apply(&zone).await;
+        Ok(())
+    }
+
+    /// Notify secondaries of and rectify a freshly patched zone, as enabled
+    /// by `NOTIFY_AFTER_APPLY` / `RECTIFY_AFTER_APPLY`.  The PATCH already
+    /// succeeded, so failures are only logged.
+    async fn after_apply(&self, zone: &ZoneRef) {
+        if self.cfg.dry_run {
+            return;
+        }
+        let actions = [
+            ("rectify", self.cfg.rectify_after_apply),
+            ("notify", self.cfg.notify_after_apply),
+        ];
+        for (action, enabled) in actions {
+            if !enabled {
+                continue;
+            }
+            let url = format!("{}/zones/{}/{}", self.base(&zone.server), zone.id, action);
+            let what = format!("PUT /zones/:id/{action}");
+            match self.send(self.http.put(&url), &what).await {
+                Ok(resp) if resp.status().is_success() => debug!("{action} {zone}: ok"),
+                Ok(resp) => {
+                    let status = resp.status();
+                    let body = self.redact(&resp.text().await.unwrap_or_default());
+                    warn!("PowerDNS {action} {zone} [{status}]: {body}");
+                }
+                Err(e) => warn!("PowerDNS {action} {zone}: {e:#}"),
+            }
+        }
+    }
+
+    /// The patch that undoes `patch`: each RRset it touches is put back as it
+    /// is now, or deleted if it doesn't exist yet.  Read it before applying
+    /// `patch`.
+    pub async fn rollback_patch(&self, patch: &ZonePatch) -> Result<ZonePatch> {
+        let zone = self.get_zone(&patch.zone_ref()).await?;
+        let rrsets = patch
+            .rrsets
+            .iter()
+            .map(|change| {
+                let prior = zone.rrsets.iter().find(|r| {
+                    r.name.eq_ignore_ascii_case(&change.name) && r.rrtype == change.rrtype
+                });
+                match prior {
+                    // Comments included: the REPLACE always sends them, so
+                    // any the failed batch wrote are put back too.
+                    Some(prior) => RrSet { changetype: Some("REPLACE".into()), ..prior.clone() },
+                    None => RrSet {
+                        records: vec![],
+                        changetype: Some("DELETE".into()),
+                        comments: vec![],
+                        ..change.clone()
+                    },
+                }
+            })
+            .collect();
+        Ok(ZonePatch { server: patch.server.clone(), zone: patch.zone.clone(), rrsets })
+    }
+
+    // ── planning ─────────────────────────────────────────────────────────────
+
+    /// Resolve every endpoint in `changes` to its zone and build the RRsets
+    /// that applying it would send, grouped per zone.
+    ///
+    /// Within each zone the order is delete → update-old → update-new →
+    /// create.  Nothing is written to PowerDNS; `apply_patch` sends each
+    /// zone's changes.
+    pub async fn plan_changes(
+        &self,
+        changes: &Changes,
+        default_ttl: u32,
+        cache: &ZoneCache,
+    ) -> Result<Vec<ZonePatch>> {
+        let mut plan: Vec<ZonePatch> = Vec::new();
+
+        let deletes = changes.delete.iter().chain(&changes.update_old);
+        for ep in deletes {
+            let zone = self.zone_for(&ep.dns_name, cache).await?;
+            let mut rrset = build_delete_rrset(ep);
+            if !self.exists(&rrset, &zone, cache).await? {
+                debug!("{} {}: already absent; nothing to delete", ep.record_type, ep.dns_name);
+                continue;
+            }
+            self.keep_disabled(&mut rrset, &zone, cache).await?;
+            if self.unchanged(&rrset, &zone, cache).await? {
+                debug!("{} {}: only disabled records left; skipping", ep.record_type, ep.dns_name);
+                continue;
+            }
+            push_rrset(&mut plan, zone, rrset);
+        }
+
+        let upserts = changes
+            .update_new
+            .iter()
+            .map(|ep| (ep, ChangeIntent::Update))
+            .chain(changes.create.iter().map(|ep| (ep, ChangeIntent::Create)));
+        for (ep, intent) in upserts {
+            let mut rrset = build_rrset(ep, default_ttl, "REPLACE", self.audit(intent), &self.cfg)?;
+            let zone = self.zone_for(&ep.dns_name, cache).await?;
+            if let Some(ttl) = self.preserved_ttl(ep, &zone, cache).await? {
+                debug!("{} {}: keeping existing TTL {ttl}", ep.record_type, ep.dns_name);
+                rrset.ttl = self.cfg.clamp_ttl(ttl);
+            }
+            self.keep_disabled(&mut rrset, &zone, cache).await?;
+            self.keep_foreign_comments(&mut rrset, &zone, cache).await?;
+            if self.unchanged(&rrset, &zone, cache).await? {
+                debug!("{} {}: already up to date; skipping", ep.record_type, ep.dns_name);
+                drop_rrset(&mut plan, &zone, &rrset);
+                continue;
+            }
+            push_rrset(&mut plan, zone, rrset);
+        }
+
+        Ok(plan)
     }
 
     // ── read ─────────────────────────────────────────────────────────────────
 
-    /// Return all managed endpoints from all zones,
-    /// optionally restricted to `domain_filter`.
+    /// Return all managed endpoints from all zones, optionally restricted to
+    /// `domain_filter` and with names under `exclude` left out.  The
+    /// configured domain regexes apply too.  A zone's apex NS RRset is left
+    /// out; NS RRsets further down are delegations and are listed.
+    ///
+    /// RRsets that end up with the same name (ignoring case) and type are
+    /// reported as one endpoint with the union of their targets and the
+    /// lower of their TTLs, so external-dns never sees duplicates.
     pub async fn list_endpoints(
         &self,
         domain_filter: &[String],
+        exclude: &[String],
     ) -> Result<Vec<Endpoint>> {
-        const MANAGED_TYPES: &[&str] = &["A", "AAAA", "CNAME", "TXT", "HTTPS"];
+        const MANAGED_TYPES: &[&str] = &[
+            "A", "AAAA", "CNAME", "TXT", "HTTPS", "MX", "SRV", "CAA", "SVCB", "ALIAS", "PTR", "NS",
+            "SSHFP", "TLSA",
+        ];
 
-        let zones = self.list_zones().await?;
-        let mut endpoints = Vec::new();
+        let zones = self.zones().await?;
+        let fetches: Vec<_> = zones.iter().map(|stub| self.fetch_managed_zone(stub)).collect();
+        let fetched: Vec<Option<Zone>> = stream::iter(fetches)
+            .buffer_unordered(self.cfg.pdns_fetch_concurrency.max(1))
+            .collect()
+            .await;
 
-        for zone_stub in zones {
-            let zone = match self.get_zone(&zone_stub.id).await {
-                Ok(z) => z,
-                Err(e) => {
-                    error!("skipping zone {}: {e}", zone_stub.id);
+        let mut endpoints: Vec<Endpoint> = Vec::new();
+        let mut seen: HashMap<(String, String), usize> = HashMap::new();
+        for zone in fetched.into_iter().flatten() {
+            for mut rrset in zone.rrsets {
+                rrset.rrtype.make_ascii_uppercase();
+                if !MANAGED_TYPES.contains(&rrset.rrtype.as_str()) {
                     continue;
                 }
-            };
-
-            for rrset in zone.rrsets {
-                if !MANAGED_TYPES.contains(&rrset.rrtype.as_str()) {
+                // The zone's own nameservers aren't external-dns's to manage;
+                // NS records below the apex are delegations, which are.
+                if rrset.rrtype == "NS" && canonical_name(&rrset.name) == canonical_name(&zone.name) {
                     continue;
                 }
 
-                let name = rrset.name.trim_end_matches('.').to_string();
+                let name = name_to_unicode(canonical_name(&rrset.name).trim_end_matches('.'));
 
-                if !domain_filter.is_empty()
-                    && !domain_filter.iter().any(|d| name.ends_with(d.as_str()))
+                if !matches_domain_filter(&name, domain_filter)
+                    || is_excluded(&name, exclude)
+                    || !self.cfg.matches_domain_regex(&name)
                 {
                     continue;
                 }
@@ -223,39 +1189,168 @@ impl PdnsClient {
                     .records
                     .iter()
                     .filter(|r| !r.disabled)
-                    .map(|r| r.content.clone())
+                    .map(|r| denormalise_target(&rrset.rrtype, &r.content))
                     .collect();
 
                 if targets.is_empty() {
                     continue;
                 }
 
+                // Report the default TTL as "unset" so it matches an
+                // external-dns source that never specified one.
+                let record_ttl = if self.cfg.omit_default_ttl_on_read
+                    && rrset.ttl == self.cfg.default_ttl
+                {
+                    0
+                } else {
+                    rrset.ttl
+                };
+
+                let key = (name.clone(), rrset.rrtype.clone());
+                if let Some(&i) = seen.get(&key) {
+                    merge_duplicate(&mut endpoints[i], targets, record_ttl, self.cfg.default_ttl);
+                    continue;
+                }
+                seen.insert(key, endpoints.len());
+
+                let meta = EndpointMeta::from_comments(&rrset.comments);
                 endpoints.push(Endpoint {
                     dns_name: name,
                     record_type: rrset.rrtype,
                     targets,
-                    record_ttl: rrset.ttl,
-                    ..Default::default()
+                    record_ttl,
+                    labels: meta.labels.into_iter().collect(),
+                    provider_specific: meta.provider_specific,
+                    set_identifier: meta.set_identifier,
                 });
             }
         }
 
         Ok(endpoints)
     }
+
+    /// Fetch a zone for `list_endpoints`, or `None` if it belongs to another
+    /// account, is suspended, or can't be read.  Errors are logged, not
+    /// returned, so one bad zone doesn't hide the rest.
+    async fn fetch_managed_zone(&self, zone_stub: &ZoneStub) -> Option<Zone> {
+        if !self.account_allowed(&zone_stub.account) {
+            debug!(
+                "skipping zone {} owned by account '{}'",
+                zone_stub.id, zone_stub.account
+            );
+            return None;
+        }
+        if !self.kind_allowed(zone_stub) {
+            debug!("skipping {} zone {}", zone_stub.kind, zone_stub.id);
+            return None;
+        }
+
+        let zone_ref = zone_stub.zone_ref();
+        match self.zone_suspended(&zone_ref).await {
+            Ok(false) => {}
+            Ok(true) => {
+                info!("skipping zone {}: suspended via metadata", zone_stub.id);
+                return None;
+            }
+            Err(e) => {
+                error!("skipping zone {}: {e}", zone_stub.id);
+                return None;
+            }
+        }
+
+        match self.get_zone(&zone_ref).await {
+            Ok(zone) => Some(zone),
+            Err(e) => {
+                error!("skipping zone {}: {e}", zone_stub.id);
+                None
+            }
+        }
+    }
 }
 
 // ─────────────────────────────────────────────────────────────────────────────
 // Helpers
 // ─────────────────────────────────────────────────────────────────────────────
 
-fn ensure_fqdn(name: &str) -> String {
-    if name.ends_with('.') {
-        name.to_string()
+/// Delay before retry number `attempt` (1-based): `base_ms` doubled per
+/// attempt, with the upper half jittered so clients that failed together
+/// don't retry together.
+fn backoff_delay(base_ms: u64, attempt: u32) -> Duration {
+    use std::hash::{BuildHasher, Hasher};
+
+    let ceiling = base_ms.saturating_mul(1u64 << (attempt - 1).min(16));
+    // RandomState is seeded randomly per instance; good enough for jitter.
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u32(attempt);
+    let jitter = hasher.finish() % (ceiling / 2 + 1);
+    Duration::from_millis(ceiling - ceiling / 2 + jitter)
+}
+
+/// Fold a second RRset with `existing`'s name and type into it: add the
+/// targets it lacks and keep the lower TTL, where 0 stands for `default_ttl`.
+fn merge_duplicate(existing: &mut Endpoint, targets: Vec<String>, ttl: u32, default_ttl: u32) {
+    let effective = |t: u32| if t == 0 { default_ttl } else { t };
+    if effective(ttl) != effective(existing.record_ttl) {
+        warn!(
+            "{} {}: duplicate RRsets with TTLs {} and {}; reporting the lower",
+            existing.record_type,
+            existing.dns_name,
+            effective(existing.record_ttl),
+            effective(ttl)
+        );
+        if effective(ttl) < effective(existing.record_ttl) {
+            existing.record_ttl = ttl;
+        }
+    }
+    for target in targets {
+        if !existing.targets.contains(&target) {
+            existing.targets.push(target);
+        }
+    }
+}
+
+/// Whether `e` failed in the HTTP/2 protocol itself, rather than e.g. a
+/// refused connection or a TLS or DNS failure.
+fn is_h2_error(e: &reqwest::Error) -> bool {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(e);
+    while let Some(err) = source {
+        if err.is::<h2::Error>() {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// `text` with every occurrence of `secret` replaced by `***`.  An empty
+/// secret leaves it alone.
+fn redact_secret(text: &str, secret: &str) -> String {
+    if secret.is_empty() {
+        text.to_string()
     } else {
-        format!("{name}.")
+        text.replace(secret, "***")
     }
 }
 
+/// Decode a successful response's JSON body.
+async fn json<T: serde::de::DeserializeOwned>(resp: Response, what: &str) -> Result<T> {
+    resp.json().await.map_err(|e| PdnsError::InvalidResponse {
+        what: what.to_string(),
+        reason: e.to_string(),
+    })
+}
+
+fn read_pem(path: &str) -> anyhow::Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("reading {path}"))
+}
+
+/// A DNS name as it is matched and written: lowercase A-labels with exactly
+/// one trailing dot.  Only for names; targets keep their case and form via
+/// `ensure_fqdn`.
+fn canonical_name(name: &str) -> String {
+    format!("{}.", name_to_ascii(name.trim_end_matches('.')))
+}
+
 /// Normalise a record's content value for PowerDNS wire format.
 ///
 /// PowerDNS requires a trailing dot on every value that is a DNS name:
@@ -263,65 +1358,383 @@ fn ensure_fqdn(name: &str) -> String {
 ///   MX     10 mail.domain  → 10 mail.domain.
 ///   NS     ns1.domain.com  → ns1.domain.com.
 ///
-/// A/AAAA records contain IP addresses – no dot needed.
-/// TXT records contain quoted strings – no dot needed.
-fn normalise_target(record_type: &str, target: &str) -> String {
+/// A/AAAA records contain IP addresses – no dot needed, nor for the hex
+/// digests of SSHFP/TLSA.
+/// TXT values are quoted and split into 255-byte strings (see `quote_txt`).
+///
+/// Surrounding whitespace is stripped first (see `trim_target`).
+pub fn normalise_target(record_type: &str, target: &str) -> String {
+    let target = trim_target(record_type, target);
     match record_type {
-        "A" | "AAAA" => target.to_string(),
-        "TXT"        => target.to_string(),
-        "HTTPS"      => normalise_https_target(target),
-        // CNAME, MX, NS, PTR, SRV, ALIAS – all name-valued types need a trailing dot
-        _            => ensure_fqdn(target),
+        "A"              => target.to_string(),
+        "AAAA"           => canonical_ipv6(target),
+        "TXT"            => quote_txt(target),
+        // CAA value is a quoted string, not a name: `0 issue "letsencrypt.org"`
+        "CAA"            => target.to_string(),
+        // Numbers and a hex digest, never a name.  PowerDNS reports the hex
+        // in lowercase, so it is written that way to compare equal.
+        "SSHFP" | "TLSA" => target.to_ascii_lowercase(),
+        "HTTPS" | "SVCB" => normalise_svc_target(target),
+        "MX"             => fqdn_after_fields(target, 1),
+        "SRV"            => fqdn_after_fields(target, 3),
+        // CNAME, NS, PTR and ALIAS (PowerDNS's CNAME-like record for the
+        // zone apex) hold a single name; anything else we don't know is
+        // treated as name-valued too.
+        _                => ensure_fqdn(target),
+    }
+}
+
+/// Re-emit an IPv6 address in the compressed form PowerDNS stores, so
+/// `2001:0db8:0:0:0:0:0:1` and `2001:db8::1` compare equal.  Anything that
+/// doesn't parse is returned unchanged for validation to reject.
+fn canonical_ipv6(target: &str) -> String {
+    target
+        .parse::<Ipv6Addr>()
+        .map_or_else(|_| target.to_string(), |addr| addr.to_string())
+}
+
+/// Fully qualify the hostname that follows `n` leading fields, leaving the
+/// fields themselves untouched:
+///   MX (n = 1)  "10 mail.example.com"       → "10 mail.example.com."
+///   SRV (n = 3) "10 60 5060 sip.example.com" → "10 60 5060 sip.example.com."
+///
+/// Content with fewer fields than expected is returned as-is (re-joined with
+/// single spaces) for PowerDNS to reject with its own error.
+fn fqdn_after_fields(target: &str, n: usize) -> String {
+    let mut fields: Vec<String> = target.split_whitespace().map(String::from).collect();
+    if fields.len() == n + 1 {
+        fields[n] = ensure_fqdn(&fields[n]);
     }
+    fields.join(" ")
 }
 
-/// Format an HTTPS SvcParam string for PowerDNS.
-/// Ensures a numeric SvcPriority is present and TargetName ends with a dot.
+/// Undo `normalise_target` for content read back from PowerDNS.
 ///
-/// Handles:
-///   "1 . alpn=h2,h3"          → "1 . alpn=h2,h3"     (already correct)
-///   "1 lb.domain.com alpn=h2" → "1 lb.domain.com. alpn=h2"
-///   "lb.domain.com"           → "1 lb.domain.com."    (bare hostname)
-fn normalise_https_target(target: &str) -> String {
-    let t = target.trim();
-    let parts: Vec<&str> = t.splitn(3, ' ').collect();
+/// external-dns stores and compares hostnames without a trailing dot, so the
+/// dot PowerDNS requires is stripped from name-valued content:
+///   CNAME  lb.domain.com.          → lb.domain.com
+///   MX     10 mail.domain.         → 10 mail.domain
+///   SRV    10 60 5060 sip.domain.  → 10 60 5060 sip.domain
+///
+/// TXT strings are unquoted and joined back into one value (see
+/// `unquote_txt`).  A/AAAA and anything else are returned unchanged, as is a
+/// bare root name (e.g. the null MX "0 .").
+pub fn denormalise_target(record_type: &str, content: &str) -> String {
+    match record_type {
+        "CNAME" | "NS" | "PTR" | "ALIAS" => strip_root_dot(content).to_string(),
+        "MX"  => strip_dot_after_fields(content, 1),
+        "SRV" => strip_dot_after_fields(content, 3),
+        "TXT" => unquote_txt(content),
+        _     => content.to_string(),
+    }
+}
 
-    if parts.len() >= 2 && parts[0].parse::<u16>().is_ok() {
-        // Already has a priority – ensure TargetName is fully qualified
-        let priority    = parts[0];
-        let target_name = ensure_fqdn(parts[1]);
-        let params      = parts.get(2).map(|p| format!(" {p}")).unwrap_or_default();
-        return format!("{priority} {target_name}{params}");
+fn strip_root_dot(name: &str) -> &str {
+    if name == "." {
+        name
+    } else {
+        name.strip_suffix('.').unwrap_or(name)
     }
+}
 
-    // No priority prefix – wrap the whole thing
-    format!("1 {}.", t.trim_end_matches('.'))
+/// Inverse of `fqdn_after_fields`.
+fn strip_dot_after_fields(content: &str, n: usize) -> String {
+    let mut fields: Vec<&str> = content.split_whitespace().collect();
+    if fields.len() == n + 1 {
+        fields[n] = strip_root_dot(fields[n]);
+    }
+    fields.join(" ")
 }
 
-fn build_rrset(ep: &Endpoint, default_ttl: u32, changetype: &str) -> RrSet {
-    let ttl = if ep.record_ttl > 0 { ep.record_ttl } else { default_ttl };
+/// Longest character-string a TXT record can hold (RFC 1035 §3.3).
+const TXT_CHUNK_BYTES: usize = 255;
+
+/// Quote a TXT value for PowerDNS, splitting it into strings of at most 255
+/// bytes:
+///   v=spf1 -all        → "v=spf1 -all"
+///   <300 bytes>        → "<255 bytes>" "<45 bytes>"
+///
+/// A value that is already quoted is unquoted first, so quoted and unquoted
+/// sources end up identical; one using escapes we don't decode (`\DDD`) is
+/// passed through as-is.  Quotes and backslashes inside are escaped, and
+/// chunks never split a UTF-8 character.
+fn quote_txt(target: &str) -> String {
+    let value = match parse_txt_strings(target) {
+        Some(value) => value,
+        None if target.starts_with('"') => return target.to_string(),
+        None => target.to_string(),
+    };
 
-    let records: Vec<Record> = ep
-        .targets
+    let mut chunks: Vec<String> = Vec::new();
+    let mut chunk = String::new();
+    for c in value.chars() {
+        if chunk.len() + c.len_utf8() > TXT_CHUNK_BYTES {
+            chunks.push(std::mem::take(&mut chunk));
+        }
+        chunk.push(c);
+    }
+    if !chunk.is_empty() || chunks.is_empty() {
+        chunks.push(chunk);
+    }
+
+    chunks
         .iter()
-        .map(|t| {
-            let content = normalise_target(&ep.record_type, t);
-            tracing::debug!(
-                record_type = %ep.record_type,
-                original    = %t,
-                normalised  = %content,
-                "normalised record content"
-            );
-            Record { content, disabled: false }
+        .map(|c| format!("\"{}\"", c.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Inverse of `quote_txt`: `"part1" "part2"` → `part1part2`.  Content that
+/// isn't a sequence of quoted strings is returned unchanged.
+fn unquote_txt(content: &str) -> String {
+    parse_txt_strings(content).unwrap_or_else(|| content.to_string())
+}
+
+/// Parse whitespace-separated quoted strings, undoing `\"` and `\\`
+/// escapes, and join them.  `None` unless the whole input is quoted strings
+/// using only those escapes.
+fn parse_txt_strings(content: &str) -> Option<String> {
+    let mut chars = content.trim().chars();
+    let mut value = String::new();
+    let mut any = false;
+    loop {
+        match chars.next() {
+            None => return any.then_some(value),
+            Some(c) if c.is_whitespace() => continue,
+            Some('"') => any = true,
+            Some(_) => return None,
+        }
+        loop {
+            match chars.next()? {
+                '"' => break,
+                '\\' => match chars.next()? {
+                    c @ ('"' | '\\') => value.push(c),
+                    _ => return None,
+                },
+                c => value.push(c),
+            }
+        }
+    }
+}
+
+/// Strip whitespace that sources sometimes leave around a target.
+///
+/// TXT content is only trimmed when it is a quoted string, and then only
+/// outside the quotes: `  "v=spf1 "  ` → `"v=spf1 "`.  An unquoted TXT value
+/// is passed through untouched since its whitespace may be meaningful.
+fn trim_target<'a>(record_type: &str, target: &'a str) -> &'a str {
+    let trimmed = target.trim();
+    if record_type != "TXT" {
+        return trimmed;
+    }
+    if trimmed.len() >= 2 && trimmed.starts_with('"') && trimmed.ends_with('"') {
+        trimmed
+    } else {
+        target
+    }
+}
+
+fn build_delete_rrset(ep: &Endpoint) -> RrSet {
+    RrSet {
+        name: canonical_name(&ep.dns_name),
+        rrtype: ep.record_type.clone(),
+        ttl: 0,
+        records: vec![],
+        changetype: Some("DELETE".into()),
+        comments: vec![],
+    }
+}
+
+/// The RRset in `zone` with `rrset`'s name and type.
+fn find_rrset<'a>(zone: &'a Zone, rrset: &RrSet) -> Option<&'a RrSet> {
+    zone.rrsets
+        .iter()
+        .find(|r| r.rrtype == rrset.rrtype && r.name.eq_ignore_ascii_case(&rrset.name))
+}
+
+/// Remove everything planned for `rrset`'s name and type in `zone`, e.g. the
+/// DELETE of an update whose new RRset turned out to be unchanged.  A patch
+/// left empty is dropped.
+fn drop_rrset(plan: &mut Vec<ZonePatch>, zone: &ZoneRef, rrset: &RrSet) {
+    for patch in plan.iter_mut().filter(|p| p.server == zone.server && p.zone == zone.id) {
+        patch.rrsets.retain(|r| !(r.name == rrset.name && r.rrtype == rrset.rrtype));
+    }
+    plan.retain(|p| !p.rrsets.is_empty());
+}
+
+/// Append `rrset` to the patch for `zone`, creating it on first use so zones
+/// keep the order in which they were first touched.
+///
+/// A REPLACE already covers removing the old RRset, so an earlier change to
+/// the same name and type (e.g. the update-old half of an update, which may
+/// be a REPLACE keeping disabled records) is dropped, as is a repeated DELETE.
+fn push_rrset(plan: &mut Vec<ZonePatch>, zone: ZoneRef, rrset: RrSet) {
+    let Some(patch) = plan.iter_mut().find(|p| p.server == zone.server && p.zone == zone.id) else {
+        plan.push(ZonePatch { server: zone.server, zone: zone.id, rrsets: vec![rrset] });
+        return;
+    };
+
+    let same = |r: &RrSet| r.name == rrset.name && r.rrtype == rrset.rrtype;
+    let is_delete = |r: &RrSet| r.changetype.as_deref() == Some("DELETE");
+
+    if is_delete(&rrset) {
+        if patch.rrsets.iter().any(|r| same(r) && is_delete(r)) {
+            return;
+        }
+    } else {
+        patch.rrsets.retain(|r| !same(r));
+    }
+    patch.rrsets.push(rrset);
+}
+
+/// Account name set on comments written by this webhook.
+const COMMENT_ACCOUNT: &str = "external-dns";
+
+/// The RRset to send PowerDNS for `ep`, with `changetype` and each target
+/// normalised by `normalise_target`.  Targets that can't be written (too
+/// many for the type, bad addresses, invalid HTTPS/SVCB) are an
+/// `InvalidRecord`.
+pub fn build_rrset(
+    ep: &Endpoint,
+    default_ttl: u32,
+    changetype: &str,
+    audit: Option<ChangeIntent>,
+    cfg: &Config,
+) -> Result<RrSet, InvalidRecord> {
+    let set_ptr = cfg.set_ptr_for_forward && matches!(ep.record_type.as_str(), "A" | "AAAA");
+
+    let ttl = if ep.record_ttl > 0 { ep.record_ttl } else { default_ttl };
+    let clamped = cfg.clamp_ttl(ttl);
+    if clamped != ttl {
+        tracing::debug!("{} {}: TTL {ttl} clamped to {clamped}", ep.record_type, ep.dns_name);
+    }
+    let ttl = clamped;
+
+    if SINGLE_VALUED_TYPES.contains(&ep.record_type.as_str()) && ep.targets.len() > 1 {
+        return Err(InvalidRecord {
+            name: ep.dns_name.clone(),
+            record_type: ep.record_type.clone(),
+            reason: format!("{} targets given but only one is allowed", ep.targets.len()),
+        });
+    }
+
+    let mut records: Vec<Record> = Vec::with_capacity(ep.targets.len());
+    for t in &ep.targets {
+        let content = normalise_target(&ep.record_type, t);
+        tracing::debug!(
+            record_type = %ep.record_type,
+            original    = %t,
+            normalised  = %content,
+            "normalised record content"
+        );
+
+        check_address_target(&ep.record_type, &content).map_err(|reason| InvalidRecord {
+            name: ep.dns_name.clone(),
+            record_type: ep.record_type.clone(),
+            reason,
+        })?;
+        if matches!(ep.record_type.as_str(), "HTTPS" | "SVCB") && cfg.validate_https_targets {
+            check_svc_target(&content).map_err(|reason| InvalidRecord {
+                name: ep.dns_name.clone(),
+                record_type: ep.record_type.clone(),
+                reason: format!("target '{content}': {reason}"),
+            })?;
+        }
+
+        records.push(Record { content, disabled: false, set_ptr });
+    }
+
+    let mut comments: Vec<Comment> = audit
+        .map(|intent| Comment {
+            content: format!("external-dns {}", intent.as_str()),
+            account: COMMENT_ACCOUNT.into(),
         })
+        .into_iter()
         .collect();
+    comments.extend(EndpointMeta::from_endpoint(ep).to_comment());
 
-    RrSet {
-        name: ensure_fqdn(&ep.dns_name),
+    Ok(RrSet {
+        name: canonical_name(&ep.dns_name),
         rrtype: ep.record_type.clone(),
         ttl,
         records,
         changetype: Some(changetype.to_string()),
-        comments: vec![],
+        comments,
+    })
+}
+
+/// Account name of the comment that carries `EndpointMeta`.
+const META_COMMENT_ACCOUNT: &str = "external-dns-meta";
+
+/// Whether the webhook wrote `comment`, as an audit or meta comment.
+fn webhook_owned(comment: &Comment) -> bool {
+    comment.account == COMMENT_ACCOUNT || comment.account == META_COMMENT_ACCOUNT
+}
+
+/// Endpoint fields PowerDNS has no place for, kept as JSON in an RRset
+/// comment so they survive a round trip through `list_endpoints`.  Without
+/// them external-dns sees e.g. the HTTPS target annotation vanish and keeps
+/// re-adjusting the record, and loses the ownership labels it registered.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EndpointMeta {
+    /// Sorted so the comment only changes when a label does.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    labels: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    provider_specific: Vec<ProviderSpecific>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    set_identifier: String,
+}
+
+impl EndpointMeta {
+    fn from_endpoint(ep: &Endpoint) -> Self {
+        Self {
+            labels: ep.labels.clone().into_iter().collect(),
+            provider_specific: ep.provider_specific.clone(),
+            set_identifier: ep.set_identifier.clone(),
+        }
+    }
+
+    /// The comment to write, or `None` when there is nothing to keep.
+    fn to_comment(&self) -> Option<Comment> {
+        if self.labels.is_empty()
+            && self.provider_specific.is_empty()
+            && self.set_identifier.is_empty()
+        {
+            return None;
+        }
+        Some(Comment {
+            content: serde_json::to_string(self).ok()?,
+            account: META_COMMENT_ACCOUNT.into(),
+        })
+    }
+
+    /// Read the metadata back from an RRset's comments.  A missing or
+    /// unparseable comment yields empty metadata.
+    fn from_comments(comments: &[Comment]) -> Self {
+        let Some(comment) = comments.iter().find(|c| c.account == META_COMMENT_ACCOUNT) else {
+            return Self::default();
+        };
+        serde_json::from_str(&comment.content).unwrap_or_else(|e| {
+            debug!("ignoring unparseable {META_COMMENT_ACCOUNT} comment: {e}");
+            Self::default()
+        })
+    }
+}
+
+/// Check an A target is an IPv4 address and an AAAA target an IPv6 one.
+/// Other types pass.
+fn check_address_target(record_type: &str, content: &str) -> std::result::Result<(), String> {
+    let valid = match record_type {
+        "A"    => content.parse::<Ipv4Addr>().is_ok(),
+        "AAAA" => content.parse::<Ipv6Addr>().is_ok(),
+        _      => true,
+    };
+    if valid {
+        Ok(())
+    } else {
+        let family = if record_type == "A" { "IPv4" } else { "IPv6" };
+        Err(format!("target '{content}' is not an {family} address"))
     }
 }
@@ -0,0 +1,112 @@
+use async_trait::async_trait;
+
+use crate::{
+    breaker::BreakerState,
+    dns::{Changes, Endpoint},
+    pdns::{PdnsClient, PdnsError, ZoneCache, ZonePatch, ZoneStub},
+};
+
+type Result<T> = std::result::Result<T, PdnsError>;
+
+// ─────────────────────────────────────────────────────────────────────────────
+// DNS provider abstraction
+// ─────────────────────────────────────────────────────────────────────────────
+//
+// The operations the HTTP handlers need from a DNS backend.  `PdnsClient` is
+// the production implementation; tests can put any other implementation in
+// `AppState` to exercise the handlers without PowerDNS.
+//
+// `ZoneCache` is created per request by the handlers and threaded through;
+// implementations that don't resolve zones can ignore it.  Errors are
+// `PdnsError`s, whose variants decide the status the handlers answer with.
+
+#[async_trait]
+pub trait DnsProvider: Send + Sync {
+    /// All managed endpoints, restricted to `domain_filter` and with names
+    /// under `exclude` left out.
+    async fn list_endpoints(
+        &self,
+        domain_filter: &[String],
+        exclude: &[String],
+    ) -> Result<Vec<Endpoint>>;
+
+    /// The RRset changes `changes` would make, grouped per zone, without
+    /// applying them.
+    async fn plan_changes(
+        &self,
+        changes: &Changes,
+        default_ttl: u32,
+        cache: &ZoneCache,
+    ) -> Result<Vec<ZonePatch>>;
+
+    /// Apply one zone's planned changes.
+    async fn apply_patch(&self, patch: &ZonePatch) -> Result<()>;
+
+    /// A patch that would undo `patch` if applied after it, built from the
+    /// zone's current state.  Used by `APPLY_MODE=atomic`.
+    async fn rollback_patch(&self, patch: &ZonePatch) -> Result<ZonePatch> {
+        Err(PdnsError::Unsupported(format!("rollback is not supported (zone {})", patch.zone)))
+    }
+
+    /// Cheap check that the backend is reachable, for readiness.
+    async fn ping(&self) -> Result<()>;
+
+    /// Every zone the backend hosts, unfiltered, for the debug endpoint.
+    async fn list_zones(&self) -> Result<Vec<ZoneStub>> {
+        Err(PdnsError::Unsupported("listing zones is not supported".into()))
+    }
+
+    /// State of the circuit breaker guarding the backend, if any.
+    fn circuit_state(&self) -> BreakerState {
+        BreakerState::Closed
+    }
+
+    /// `text` with any backend secret masked, for logging request bodies.
+    fn redact(&self, text: &str) -> String {
+        text.to_string()
+    }
+}
+
+#[async_trait]
+impl DnsProvider for PdnsClient {
+    async fn list_endpoints(
+        &self,
+        domain_filter: &[String],
+        exclude: &[String],
+    ) -> Result<Vec<Endpoint>> {
+        PdnsClient::list_endpoints(self, domain_filter, exclude).await
+    }
+
+    async fn plan_changes(
+        &self,
+        changes: &Changes,
+        default_ttl: u32,
+        cache: &ZoneCache,
+    ) -> Result<Vec<ZonePatch>> {
+        PdnsClient::plan_changes(self, changes, default_ttl, cache).await
+    }
+
+    async fn apply_patch(&self, patch: &ZonePatch) -> Result<()> {
+        PdnsClient::apply_patch(self, patch).await
+    }
+
+    async fn rollback_patch(&self, patch: &ZonePatch) -> Result<ZonePatch> {
+        PdnsClient::rollback_patch(self, patch).await
+    }
+
+    async fn ping(&self) -> Result<()> {
+        PdnsClient::ping(self).await
+    }
+
+    async fn list_zones(&self) -> Result<Vec<ZoneStub>> {
+        PdnsClient::list_zones(self).await
+    }
+
+    fn circuit_state(&self) -> BreakerState {
+        PdnsClient::circuit_state(self)
+    }
+
+    fn redact(&self, text: &str) -> String {
+        PdnsClient::redact(self, text)
+    }
+}
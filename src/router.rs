@@ -0,0 +1,130 @@
+use axum::{
+    body::Body,
+    extract::{DefaultBodyLimit, Request, State},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Router,
+};
+use http_body_util::{BodyExt, LengthLimitError};
+use tower_http::{catch_panic::CatchPanicLayer, limit::RequestBodyLimitLayer, trace::TraceLayer};
+use tracing::{debug, info};
+
+use crate::{auth, handlers, AppState};
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Router
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// The webhook's full router, with every middleware the server runs.
+pub fn app(state: AppState) -> Router {
+    // Probes, /status and /version stay outside the auth and timeout layers so kubelet
+    // needs no token and a slow sync never fails them.
+    let probes = Router::new()
+        .route("/healthz",         get(handlers::healthz))
+        .route("/readyz",          get(handlers::readyz))
+        .route("/status",          get(handlers::status))
+        .route("/version",         get(handlers::version));
+
+    let mut app = Router::new()
+        .route("/",                get(handlers::negotiate))
+        .route("/records",         get(handlers::get_records))
+        .route("/records",         post(handlers::apply_changes))
+        .route("/records/preview", post(handlers::preview_changes))
+        .route("/adjustendpoints", post(handlers::adjust_endpoints));
+    if state.cfg.enable_debug_endpoints {
+        info!("Debug endpoints enabled: GET /zones");
+        app = app.route("/zones",  get(handlers::debug_zones));
+    }
+    #[cfg(feature = "test-panic")]
+    {
+        app = app.route("/test/panic", get(panicking));
+    }
+
+    // Route layers run last-added first: auth, then body logging, so an
+    // unauthenticated body is never read or logged, then the timeout.
+    let max_body_bytes = state.cfg.max_body_bytes;
+    app.route_layer(middleware::from_fn_with_state(state.clone(), handlers::handler_timeout))
+        .route_layer(middleware::from_fn_with_state(state.clone(), log_request_body))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::require_bearer))
+        .merge(probes)
+        // Replaces axum's 2 MB Json limit so large syncs are bounded only by
+        // MAX_BODY_BYTES, enforced before the body is read for logging.
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(max_body_bytes))
+        .layer(CatchPanicLayer::custom(handlers::panic_response))
+        .layer(TraceLayer::new_for_http())
+        .with_state(state)
+}
+
+/// Always panics; routed only with the `test-panic` feature, so tests can
+/// check the real stack turns a panic into a 500.
+#[cfg(feature = "test-panic")]
+async fn panicking() -> StatusCode {
+    panic!("boom")
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Request body logging middleware
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Whether a body read failed because it ran past `RequestBodyLimitLayer`;
+/// chunked bodies only hit the limit once they are collected here.
+fn is_length_limit(e: &axum::Error) -> bool {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(e);
+    while let Some(err) = source {
+        if err.is::<LengthLimitError>() {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// A request body as it is logged: pretty-printed when it is JSON, cut
+/// short after `max_bytes` (0 for no limit).
+pub fn render_body(bytes: &[u8], max_bytes: usize) -> String {
+    if max_bytes > 0 && bytes.len() > max_bytes {
+        // Pretty-printing megabytes of JSON costs more than it tells.
+        let head = String::from_utf8_lossy(&bytes[..max_bytes]);
+        return format!("{head}… <{} bytes, truncated>", bytes.len());
+    }
+    std::str::from_utf8(bytes)
+        .map(|s| {
+            serde_json::from_str::<serde_json::Value>(s)
+                .map(|v| serde_json::to_string_pretty(&v).unwrap_or_else(|_| s.to_string()))
+                .unwrap_or_else(|_| s.to_string())
+        })
+        .unwrap_or_else(|_| format!("<{} binary bytes>", bytes.len()))
+}
+
+/// Log each request body at DEBUG, with the provider's secrets redacted in
+/// case a client ever sends them.  Bodies over `LOG_BODY_MAX_BYTES` are cut
+/// short; the handler always gets all of it.
+async fn log_request_body(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let (parts, body) = req.into_parts();
+
+    let bytes = match body.collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(e) if is_length_limit(&e) => {
+            return (StatusCode::PAYLOAD_TOO_LARGE, "request body exceeds MAX_BODY_BYTES").into_response();
+        }
+        Err(e) => {
+            tracing::error!("failed to read request body: {e}");
+            return next.run(Request::from_parts(parts, Body::empty())).await;
+        }
+    };
+
+    if tracing::enabled!(tracing::Level::DEBUG) {
+        let body_str = render_body(&bytes, state.cfg.log_body_max_bytes);
+        debug!(
+            method = %parts.method,
+            path   = %parts.uri.path(),
+            body   = %state.provider.redact(&body_str),
+            "← request body"
+        );
+    }
+
+    next.run(Request::from_parts(parts, Body::from(bytes))).await
+}
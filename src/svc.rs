@@ -0,0 +1,121 @@
+// HTTPS and SVCB targets (RFC 9460): `<SvcPriority> <TargetName> [params]`.
+//
+// One implementation shared by the handlers, which fill targets in from
+// annotations in /adjustendpoints, and `pdns`, which writes them, so the
+// target external-dns is told about is the one that is stored and read back.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::dns::ensure_fqdn;
+
+/// Ensures a numeric SvcPriority is present and TargetName ends with a dot.
+///
+/// Handles:
+///   "1 . alpn=h2,h3"          → "1 . alpn=h2,h3"            (already correct)
+///   "1 lb.domain.com alpn=h2" → "1 lb.domain.com. alpn=h2"
+///   "lb.domain.com"           → "1 lb.domain.com."           (bare hostname)
+///   "lb.domain.com alpn=h2"   → "1 lb.domain.com. alpn=h2"   (no priority)
+///   "alpn=h2,h3"              → "1 . alpn=h2,h3"             (bare params)
+pub fn normalise_svc_target(target: &str) -> String {
+    let t = target.trim();
+    let parts: Vec<&str> = t.splitn(3, ' ').collect();
+
+    if parts.len() >= 2 && parts[0].parse::<u16>().is_ok() {
+        // Already has a priority – ensure TargetName is fully qualified
+        let priority    = parts[0];
+        let target_name = ensure_fqdn(parts[1]);
+        let params      = parts.get(2).map(|p| format!(" {p}")).unwrap_or_default();
+        return format!("{priority} {target_name}{params}");
+    }
+
+    // No priority prefix – the TargetName is "." unless a hostname leads
+    let (first, rest) = t.split_once(' ').unwrap_or((t, ""));
+    let params = if rest.is_empty() { String::new() } else { format!(" {rest}") };
+    if is_svc_param(first) {
+        format!("1 . {t}")
+    } else {
+        format!("1 {}{params}", ensure_fqdn(first))
+    }
+}
+
+/// Whether a token of an HTTPS/SVCB target is a SvcParam rather than a
+/// priority or TargetName.
+pub fn is_svc_param(token: &str) -> bool {
+    token.contains('=') || token == "no-default-alpn"
+}
+
+/// Check a normalised HTTPS or SVCB record (`<priority> <target> [params]`)
+/// has a TargetName that is either `.` (AliasMode / "same name") or a
+/// syntactically valid hostname, and valid SvcParams.
+pub fn check_svc_target(content: &str) -> std::result::Result<(), String> {
+    let mut parts = content.split_whitespace();
+    let priority = parts.next().unwrap_or_default();
+    if priority.parse::<u16>().is_err() {
+        return Err(format!("SvcPriority '{priority}' is not a number"));
+    }
+    match parts.next() {
+        None => return Err("missing TargetName".into()),
+        Some(".") => {}
+        Some(target) if is_valid_hostname(target) => {}
+        Some(target) => {
+            return Err(format!("TargetName '{target}' is not '.' or a valid hostname"))
+        }
+    }
+    check_svc_params(content)
+}
+
+/// SvcParamKeys accepted in HTTPS/SVCB targets (RFC 9460 §14.3.2).
+const SVC_PARAM_KEYS: &[&str] =
+    &["mandatory", "alpn", "no-default-alpn", "port", "ipv4hint", "ech", "ipv6hint"];
+
+/// Check the `key=value` SvcParams after the priority and TargetName of an
+/// HTTPS/SVCB target: keys must be known (so a typo like `alnp` is caught),
+/// and `port`, the address hints and `mandatory` must parse.
+pub fn check_svc_params(content: &str) -> std::result::Result<(), String> {
+    for param in content.split_whitespace().skip(2) {
+        let (key, value) = match param.split_once('=') {
+            Some((key, value)) => (key, Some(value.trim_matches('"'))),
+            None => (param, None),
+        };
+        if !SVC_PARAM_KEYS.contains(&key) {
+            return Err(format!("unknown SvcParam key '{key}'"));
+        }
+
+        let Some(value) = value.filter(|v| !v.is_empty()) else {
+            if key == "no-default-alpn" {
+                continue;
+            }
+            return Err(format!("SvcParam '{key}' needs a value"));
+        };
+        let list = || value.split(',');
+        let valid = match key {
+            "no-default-alpn" => false,
+            "port"            => value.parse::<u16>().is_ok(),
+            "ipv4hint"        => list().all(|a| a.parse::<Ipv4Addr>().is_ok()),
+            "ipv6hint"        => list().all(|a| a.parse::<Ipv6Addr>().is_ok()),
+            "mandatory"       => list().all(|k| SVC_PARAM_KEYS.contains(&k) && k != "mandatory"),
+            _                 => true,
+        };
+        if !valid {
+            return Err(format!("invalid SvcParam '{param}'"));
+        }
+    }
+    Ok(())
+}
+
+/// Letters, digits, '-' and '_' in 1–63 byte labels that don't start or end
+/// with '-', at most 253 bytes overall; one trailing dot allowed.
+pub fn is_valid_hostname(name: &str) -> bool {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    !name.is_empty()
+        && name.len() <= 253
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
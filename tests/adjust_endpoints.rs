@@ -20,12 +20,21 @@ use tower::ServiceExt; // for `.oneshot()`
 // This avoids needing a real PowerDNS server or secret files.
 // ─────────────────────────────────────────────────────────────────────────────
 
-fn test_router() -> Router {
-    // Import the handler directly; no AppState needed for adjustendpoints.
+fn test_router(extra: &[(&str, &str)]) -> Router {
     use axum::routing::post;
-    use pdns_webhook::handlers;
+    use pdns_webhook::{config::Config, handlers, pdns::PdnsClient, AppState};
 
-    Router::new().route("/adjustendpoints", post(handlers::adjust_endpoints))
+    let mut vars = vec![
+        ("PDNS_API_KEY".to_string(), "secret".to_string()),
+        ("PDNS_API_KEY_FILE".to_string(), "/nonexistent".to_string()),
+    ];
+    vars.extend(extra.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+    let cfg = Config::from_vars(vars).unwrap();
+
+    // The client is never called by /adjustendpoints.
+    Router::new()
+        .route("/adjustendpoints", post(handlers::adjust_endpoints))
+        .with_state(AppState::new(cfg.clone(), PdnsClient::new(cfg).unwrap()))
 }
 
 // ─────────────────────────────────────────────────────────────────────────────
@@ -34,7 +43,12 @@ fn test_router() -> Router {
 
 /// Send a POST /adjustendpoints and return the parsed JSON response body.
 async fn post_adjust(body: Value) -> (StatusCode, Value) {
-    let app = test_router();
+    post_adjust_with(&[], body).await
+}
+
+/// `post_adjust` against a webhook configured with `extra` env-style vars.
+async fn post_adjust_with(extra: &[(&str, &str)], body: Value) -> (StatusCode, Value) {
+    let app = test_router(extra);
 
     let request = Request::builder()
         .method("POST")
@@ -165,6 +179,198 @@ async fn test_adjust_https_without_annotation_normalises_targets() {
     assert_eq!(target, "1 svc.example.com. alpn=h2");
 }
 
+/// The SVCB annotation is handled exactly like the HTTPS one, and only
+/// annotated endpoints get an SVCB record.
+#[tokio::test]
+async fn test_adjust_svcb_target_from_annotation() {
+    let body = json!([
+        {
+            "dnsName": "svc.example.com",
+            "providerSpecific": [{ "name": "webhook/pdns-svcb-target", "value": "1 . alpn=h2" }],
+            "recordType": "A",
+            "targets": ["10.0.0.1"]
+        },
+        {
+            "dnsName": "plain.example.com",
+            "recordType": "A",
+            "targets": ["10.0.0.2"]
+        }
+    ]);
+
+    let (status, response) = post_adjust(body).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let svcb: Vec<&Value> = response
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|ep| ep["recordType"] == "SVCB")
+        .collect();
+    assert_eq!(svcb.len(), 1, "{response}");
+    assert_eq!(svcb[0]["dnsName"], "svc.example.com");
+    assert_eq!(svcb[0]["targets"], json!(["1 . alpn=h2"]));
+
+    // An SVCB endpoint comes back once, as the derived record: returned next
+    // to the original it would be planned as a second change to the RRset.
+    let body = json!([{
+        "dnsName": "svc.example.com",
+        "providerSpecific": [{ "name": "webhook/pdns-svcb-target", "value": "1 . alpn=h3" }],
+        "recordType": "SVCB",
+        "targets": ["1 . alpn=h2"]
+    }]);
+    let (_, response) = post_adjust(body).await;
+    let svcb: Vec<&Value> = response
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|ep| ep["recordType"] == "SVCB")
+        .collect();
+    assert_eq!(svcb.len(), 1, "{response}");
+    assert_eq!(svcb[0]["targets"], json!(["1 . alpn=h3"]));
+}
+
+/// An annotation with a bogus SvcParam is ignored rather than turned into a
+/// broken record, leaving the HTTPS record the endpoint would get without
+/// it; a well-formed one is kept.
+#[tokio::test]
+async fn test_adjust_annotation_with_bad_svc_params_is_skipped() {
+    let annotated = |value: &str| json!({
+        "dnsName": "app.example.com",
+        "providerSpecific": [{ "name": "webhook/pdns-https-target", "value": value }],
+        "recordType": "A",
+        "targets": ["10.0.0.1"]
+    });
+    let (_, plain) = post_adjust(json!([{
+        "dnsName": "app.example.com",
+        "recordType": "A",
+        "targets": ["10.0.0.1"]
+    }]))
+    .await;
+
+    for bogus in ["1 . alnp=h2", "1 . ipv4hint=10.0.0.1,not-an-ip", "1 . ipv6hint=10.0.0.1"] {
+        let (status, response) = post_adjust(json!([annotated(bogus)])).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(response.as_array().unwrap().len(), 2, "{bogus}: {response}");
+        assert_eq!(response[1]["recordType"], "HTTPS", "{bogus}: {response}");
+        assert_eq!(response[1]["targets"], plain[1]["targets"], "{bogus}: {response}");
+    }
+
+    let valid = "1 . alpn=h2,h3 port=443 ipv4hint=10.0.0.1,10.0.0.2";
+    let (_, response) = post_adjust(json!([annotated(valid)])).await;
+    assert_eq!(response[1]["recordType"], "HTTPS");
+    assert_eq!(response[1]["targets"], json!([valid]));
+}
+
+/// A bad SVCB annotation on an SVCB endpoint drops only the override: the
+/// record keeps the endpoint's own target instead of vanishing.
+#[tokio::test]
+async fn test_adjust_invalid_svcb_annotation_keeps_own_targets() {
+    let body = json!([{
+        "dnsName": "svc.example.com",
+        "providerSpecific": [{ "name": "webhook/pdns-svcb-target", "value": "1 . alnp=h2" }],
+        "recordType": "SVCB",
+        "targets": ["svc.example.net alpn=h2"]
+    }]);
+
+    let (status, response) = post_adjust(body).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Only the derived record, with the endpoint's target normalised,
+    // replacing the original rather than sitting next to it.
+    let svcb: Vec<&Value> = response
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|ep| ep["recordType"] == "SVCB")
+        .collect();
+    assert_eq!(svcb.len(), 1, "{response}");
+    assert_eq!(svcb[0]["targets"], json!(["1 svc.example.net. alpn=h2"]));
+}
+
+/// HTTPS_TARGET_ANNOTATION replaces the providerSpecific key read for the
+/// HTTPS target; the default key is then ignored.
+#[tokio::test]
+async fn test_adjust_https_annotation_key_configurable() {
+    let body = json!([{
+        "dnsName": "app.example.com",
+        "providerSpecific": [
+            { "name": "webhook/acme-https", "value": "1 . alpn=h3" },
+            { "name": "webhook/pdns-https-target", "value": "1 . alpn=h2" }
+        ],
+        "recordType": "HTTPS",
+        "targets": []
+    }]);
+
+    let (status, response) =
+        post_adjust_with(&[("HTTPS_TARGET_ANNOTATION", "webhook/acme-https")], body).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response[0]["recordType"], "HTTPS");
+    assert_eq!(response.as_array().unwrap().len(), 1, "{response}");
+    assert_eq!(response[0]["targets"], json!(["1 . alpn=h3"]));
+}
+
+/// With the default config, an HTTPS target missing its SvcPriority gets
+/// "1" prepended, as before the handler took any state.
+#[tokio::test]
+async fn test_adjust_https_target_without_priority_gets_one() {
+    let body = json!([{
+        "dnsName": "app.example.com",
+        "recordType": "HTTPS",
+        "targets": ["svc.example.com. alpn=h2"]
+    }]);
+
+    let (status, response) = post_adjust(body).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response[0]["recordType"], "HTTPS");
+    assert_eq!(response[0]["targets"], json!(["1 svc.example.com. alpn=h2"]));
+
+    // Bare SvcParams get the "." TargetName, from targets or annotation.
+    let body = json!([{
+        "dnsName": "app.example.com",
+        "providerSpecific": [{ "name": "webhook/pdns-https-target", "value": "alpn=h3" }],
+        "recordType": "HTTPS",
+        "targets": []
+    }, {
+        "dnsName": "api.example.com",
+        "recordType": "HTTPS",
+        "targets": ["alpn=h2"]
+    }]);
+    let (_, response) = post_adjust(body).await;
+    assert_eq!(response.as_array().unwrap().len(), 2, "{response}");
+    assert_eq!(response[0]["targets"], json!(["1 . alpn=h3"]));
+    assert_eq!(response[1]["targets"], json!(["1 . alpn=h2"]));
+}
+
+/// DEFAULT_HTTPS_TARGET fills in an HTTPS endpoint with neither annotation
+/// nor targets, but never overrides either.
+#[tokio::test]
+async fn test_adjust_default_https_target() {
+    let https = |targets: Value, provider_specific: Value| json!({
+        "dnsName": "app.example.com",
+        "providerSpecific": provider_specific,
+        "recordType": "HTTPS",
+        "targets": targets
+    });
+    let cfg = [("DEFAULT_HTTPS_TARGET", "1 . alpn=h2,h3")];
+    let annotation = json!([{ "name": "webhook/pdns-https-target", "value": "1 . alpn=h2" }]);
+
+    let cases = [
+        (https(json!([]), json!([])), "1 . alpn=h2,h3"),
+        (https(json!(["2 svc.example.com."]), json!([])), "2 svc.example.com."),
+        (https(json!([]), annotation), "1 . alpn=h2"),
+    ];
+    for (endpoint, expected) in cases {
+        let (status, response) = post_adjust_with(&cfg, json!([endpoint])).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(response.as_array().unwrap().len(), 1, "{response}");
+        assert_eq!(response[0]["targets"], json!([expected]), "{response}");
+    }
+
+    // Unset, an empty HTTPS endpoint is skipped as before.
+    let (_, response) = post_adjust(json!([https(json!([]), json!([]))])).await;
+    assert_eq!(response.as_array().unwrap().len(), 1);
+}
+
 /// Empty input returns an empty array (not an error).
 #[tokio::test]
 async fn test_adjust_empty_array() {
@@ -172,3 +378,27 @@ async fn test_adjust_empty_array() {
     assert_eq!(status, StatusCode::OK);
     assert_eq!(response, json!([]));
 }
+
+/// The empty-input response is exactly `[]` and still carries the webhook
+/// Content-Type external-dns requires.
+#[tokio::test]
+async fn test_adjust_empty_array_has_webhook_content_type() {
+    let request = Request::builder()
+        .method("POST")
+        .uri("/adjustendpoints")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from("[]"))
+        .unwrap();
+
+    let response = test_router(&[]).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/external.dns.webhook+json;version=1"
+    );
+
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(&bytes[..], b"[]");
+}
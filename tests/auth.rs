@@ -0,0 +1,48 @@
+// tests/auth.rs
+//
+// Tests for the optional bearer-token layer in front of the webhook routes.
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use pdns_webhook::{config::Config, pdns::PdnsClient, AppState};
+use tower::ServiceExt; // for `.oneshot()`
+
+fn app(extra: &[(&str, &str)]) -> Router {
+    let mut vars = vec![
+        ("PDNS_API_KEY".to_string(), "secret".to_string()),
+        ("PDNS_API_KEY_FILE".to_string(), "/nonexistent".to_string()),
+    ];
+    vars.extend(extra.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+    let cfg = Config::from_vars(vars).unwrap();
+    pdns_webhook::app(AppState::new(cfg.clone(), PdnsClient::new(cfg).unwrap()))
+}
+
+async fn status(app: &Router, uri: &str, token: Option<&str>) -> StatusCode {
+    let mut request = Request::builder().uri(uri);
+    if let Some(token) = token {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+    }
+    let response = app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+    response.status()
+}
+
+#[tokio::test]
+async fn test_bearer_token_required_when_configured() {
+    let app = app(&[("WEBHOOK_AUTH_TOKEN", "s3cret")]);
+
+    assert_eq!(status(&app, "/", None).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(status(&app, "/", Some("wrong")).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(status(&app, "/", Some("s3cret")).await, StatusCode::OK);
+    assert_eq!(status(&app, "/healthz", None).await, StatusCode::OK);
+    assert_eq!(status(&app, "/version", None).await, StatusCode::OK);
+    assert_eq!(status(&app, "/status", None).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_no_token_configured_leaves_routes_open() {
+    let app = app(&[]);
+    assert_eq!(status(&app, "/", None).await, StatusCode::OK);
+}
@@ -0,0 +1,114 @@
+// tests/circuit_breaker.rs
+//
+// Integration test: with PowerDNS unreachable, /readyz fails, the circuit
+// breaker opens after PDNS_CB_FAILURE_THRESHOLD failures, further calls fail
+// fast with 503, and /readyz reports the open circuit.  The breaker's
+// half-open probe and failure window are tested on `CircuitBreaker` itself.
+
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    routing::get,
+    Router,
+};
+use pdns_webhook::{
+    breaker::{BreakerState, CircuitBreaker},
+    config::Config,
+    handlers,
+    pdns::PdnsClient,
+    AppState,
+};
+use serde_json::Value;
+use tower::ServiceExt; // for `.oneshot()`
+
+/// A localhost URL nothing is listening on.
+async fn dead_url() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+    format!("http://{addr}")
+}
+
+async fn send_get(app: &Router, uri: &str) -> (StatusCode, Value) {
+    let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn test_breaker_opens_after_threshold_and_fails_fast() {
+    let cfg = Config::from_vars([
+        ("PDNS_API_URL".to_string(), dead_url().await),
+        ("PDNS_API_KEY".to_string(), "secret".to_string()),
+        ("PDNS_API_KEY_FILE".to_string(), "/nonexistent".to_string()),
+        ("PDNS_CB_FAILURE_THRESHOLD".to_string(), "2".to_string()),
+        ("PDNS_CB_COOLDOWN_SECONDS".to_string(), "60".to_string()),
+        ("PDNS_MAX_RETRIES".to_string(), "0".to_string()),
+    ])
+    .unwrap();
+    let pdns = PdnsClient::new(cfg.clone()).unwrap();
+    let app = Router::new()
+        .route("/records", get(handlers::get_records))
+        .route("/readyz", get(handlers::readyz))
+        .with_state(AppState::new(cfg, pdns));
+
+    // The readiness probe's own connection failure counts towards the
+    // threshold…
+    let (status, body) = send_get(&app, "/readyz").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["circuit"], "closed");
+    assert!(body["error"].as_str().unwrap().contains("GET /servers/:id"));
+
+    // …and a second one trips the breaker…
+    let (status, _) = send_get(&app, "/records").await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+
+    // …after which calls fail fast with a distinct error.
+    let (status, body) = send_get(&app, "/records").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(body["error"].as_str().unwrap().contains("circuit breaker is open"));
+
+    let (status, body) = send_get(&app, "/readyz").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["circuit"], "open");
+    assert!(body["error"].as_str().unwrap().contains("circuit breaker is open"));
+}
+
+/// Once the cooldown is over exactly one probe is let through; the rest
+/// keep failing fast until it reports back.
+#[test]
+fn test_half_open_lets_one_probe_through() {
+    let breaker = CircuitBreaker::new(1, Duration::from_millis(50), Duration::ZERO);
+    breaker.record_failure();
+    assert!(breaker.check().is_err());
+
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(breaker.state(), BreakerState::HalfOpen);
+    assert!(breaker.check().is_ok());
+    assert!(breaker.check().is_err(), "a second call got through while probing");
+
+    breaker.record_success();
+    assert_eq!(breaker.state(), BreakerState::Closed);
+    assert!(breaker.check().is_ok());
+    assert!(breaker.check().is_ok());
+}
+
+/// Failures further apart than the window don't add up; ones inside it do.
+#[test]
+fn test_failures_only_count_within_window() {
+    let breaker = CircuitBreaker::new(2, Duration::from_secs(60), Duration::from_millis(50));
+    breaker.record_failure();
+    std::thread::sleep(Duration::from_millis(60));
+    breaker.record_failure();
+    assert_eq!(breaker.state(), BreakerState::Closed);
+
+    breaker.record_failure();
+    assert_eq!(breaker.state(), BreakerState::Open);
+    assert!(breaker.check().unwrap_err().retry_in <= Duration::from_secs(60));
+}
@@ -0,0 +1,408 @@
+// tests/common/mod.rs
+//
+// A tiny in-process stand-in for the PowerDNS HTTP API, built on axum so the
+// tests need nothing beyond what the crate already depends on.
+//
+// It keeps zones in memory, applies REPLACE/DELETE PATCHes the way PowerDNS
+// does, and records every request it receives so tests can assert on the
+// calls the webhook made.
+
+#![allow(dead_code)]
+
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::{get, put},
+    Json, Router,
+};
+use pdns_webhook::config::Config;
+use serde_json::{json, Value};
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Mock state
+// ─────────────────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone)]
+pub struct MockZone {
+    /// Server id the zone is listed under.
+    pub server: String,
+    pub id: String,
+    pub name: String,
+    pub account: String,
+    /// Native, Master, Slave, ...
+    pub kind: String,
+    pub metadata: BTreeMap<String, Vec<String>>,
+    pub rrsets: Vec<Value>,
+}
+
+#[derive(Debug, Default)]
+pub struct MockState {
+    /// Zones keyed by id.
+    pub zones: BTreeMap<String, MockZone>,
+    /// Every request seen, as "METHOD /path".
+    pub requests: Vec<String>,
+    /// Headers of every request seen, in order.
+    pub headers: Vec<HeaderMap>,
+    /// Body of every PATCH received, in order.
+    pub patches: Vec<Value>,
+    /// Id to give the next zone created via POST, instead of its name.
+    pub next_zone_id: Option<String>,
+    /// Statuses to answer the next requests with, in order, before serving
+    /// normally again.
+    pub fail_with: VecDeque<u16>,
+    /// Delay before answering every request.
+    pub delay: Option<Duration>,
+    /// PATCHes currently being answered, and the most there ever were at
+    /// once.
+    pub patches_in_flight: usize,
+    pub max_patches_in_flight: usize,
+    /// Zone ids whose PATCHes are rejected with 422.
+    pub reject_patches: BTreeSet<String>,
+    /// Answer every notify/rectify with 422, as for a non-primary zone.
+    pub reject_zone_actions: bool,
+}
+
+#[derive(Clone)]
+pub struct MockPdns {
+    pub url: String,
+    pub state: Arc<Mutex<MockState>>,
+}
+
+impl MockPdns {
+    /// Start a mock PowerDNS on an ephemeral localhost port.
+    pub async fn start() -> Self {
+        let state = Arc::new(Mutex::new(MockState::default()));
+
+        let app = Router::new()
+            .route("/api/v1/servers/:server", get(get_server))
+            .route("/api/v1/servers/:server/zones", get(list_zones).post(create_zone))
+            .route(
+                "/api/v1/servers/:server/zones/:zone",
+                get(get_zone).patch(patch_zone),
+            )
+            .route(
+                "/api/v1/servers/:server/zones/:zone/metadata/:kind",
+                get(get_metadata),
+            )
+            .route("/api/v1/servers/:server/zones/:zone/notify", put(zone_action))
+            .route("/api/v1/servers/:server/zones/:zone/rectify", put(zone_action))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), record_request))
+            .with_state(state.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        Self { url: format!("http://{addr}"), state }
+    }
+
+    /// Add a zone whose id is its name, e.g. `example.com.`, on the default
+    /// server `localhost`.
+    pub fn add_zone(&self, name: &str, rrsets: Vec<Value>) {
+        self.add_zone_on("localhost", name, rrsets);
+    }
+
+    /// Add a zone whose id is its name to server `server`.
+    pub fn add_zone_on(&self, server: &str, name: &str, rrsets: Vec<Value>) {
+        let name = fqdn(name);
+        self.state.lock().unwrap().zones.insert(
+            name.clone(),
+            MockZone {
+                server: server.to_string(),
+                id: name.clone(),
+                name,
+                account: String::new(),
+                kind: "Native".into(),
+                metadata: BTreeMap::new(),
+                rrsets,
+            },
+        );
+    }
+
+    /// Set the PowerDNS account owning a zone.
+    pub fn set_account(&self, zone: &str, account: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.zones.get_mut(&fqdn(zone)).unwrap().account = account.to_string();
+    }
+
+    /// Set a zone's kind, e.g. "Slave".
+    pub fn set_kind(&self, zone: &str, kind: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.zones.get_mut(&fqdn(zone)).unwrap().kind = kind.to_string();
+    }
+
+    /// Set a metadata kind on a zone.
+    pub fn set_metadata(&self, zone: &str, kind: &str, values: &[&str]) {
+        let mut state = self.state.lock().unwrap();
+        let values = values.iter().map(|v| v.to_string()).collect();
+        state.zones.get_mut(&fqdn(zone)).unwrap().metadata.insert(kind.to_string(), values);
+    }
+
+    /// Answer the next requests with `statuses`, one each, before serving
+    /// normally again.
+    pub fn fail_next(&self, statuses: &[u16]) {
+        self.state.lock().unwrap().fail_with.extend(statuses);
+    }
+
+    /// Reject every PATCH to `zone` with 422.
+    pub fn reject_patches(&self, zone: &str) {
+        self.state.lock().unwrap().reject_patches.insert(fqdn(zone));
+    }
+
+    /// Current RRsets of a zone.
+    pub fn rrsets(&self, zone: &str) -> Vec<Value> {
+        self.state.lock().unwrap().zones[&fqdn(zone)].rrsets.clone()
+    }
+
+    pub fn requests(&self) -> Vec<String> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    pub fn patches(&self) -> Vec<Value> {
+        self.state.lock().unwrap().patches.clone()
+    }
+
+    /// Value of header `name` on every request seen, "" where it was absent.
+    pub fn header(&self, name: &str) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state
+            .headers
+            .iter()
+            .map(|h| h.get(name).and_then(|v| v.to_str().ok()).unwrap_or_default().to_string())
+            .collect()
+    }
+
+    /// Number of requests received matching "METHOD /path" exactly.
+    pub fn count(&self, request: &str) -> usize {
+        self.requests().iter().filter(|r| *r == request).count()
+    }
+
+    /// A `Config` pointing at this mock, with `extra` env-style overrides
+    /// replacing the defaults they name.
+    pub fn config(&self, extra: &[(&str, &str)]) -> Config {
+        let mut vars: Vec<(String, String)> = [
+            ("PDNS_API_URL", self.url.as_str()),
+            ("PDNS_API_KEY", "secret"),
+            ("PDNS_API_KEY_FILE", "/nonexistent"),
+        ]
+        .into_iter()
+        .filter(|(k, _)| !extra.iter().any(|(name, _)| name == k))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        vars.extend(extra.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+        Config::from_vars(vars).unwrap()
+    }
+}
+
+/// Build a PowerDNS RRset JSON value.
+pub fn rrset(name: &str, rrtype: &str, ttl: u32, contents: &[&str]) -> Value {
+    json!({
+        "name": fqdn(name),
+        "type": rrtype,
+        "ttl": ttl,
+        "records": contents
+            .iter()
+            .map(|c| json!({ "content": c, "disabled": false }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+fn fqdn(name: &str) -> String {
+    if name.ends_with('.') { name.to_string() } else { format!("{name}.") }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Mock handlers
+// ─────────────────────────────────────────────────────────────────────────────
+
+type Shared = Arc<Mutex<MockState>>;
+
+async fn record_request(
+    State(state): State<Shared>,
+    method: Method,
+    uri: Uri,
+    req: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Response {
+    let (fail, delay) = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(format!("{method} {}", uri.path()));
+        state.headers.push(req.headers().clone());
+        if method == Method::PATCH {
+            state.patches_in_flight += 1;
+            state.max_patches_in_flight = state.max_patches_in_flight.max(state.patches_in_flight);
+        }
+        (state.fail_with.pop_front(), state.delay)
+    };
+    if let Some(delay) = delay {
+        tokio::time::sleep(delay).await;
+    }
+    let response = match fail {
+        Some(status) => {
+            let status = StatusCode::from_u16(status).unwrap();
+            (status, Json(json!({ "error": "injected failure" }))).into_response()
+        }
+        None => next.run(req).await,
+    };
+    if method == Method::PATCH {
+        state.lock().unwrap().patches_in_flight -= 1;
+    }
+    response
+}
+
+/// The default server id `localhost` exists, as does any server a zone was
+/// added to.
+async fn get_server(State(state): State<Shared>, Path(server): Path<String>) -> Response {
+    let known =
+        server == "localhost" || state.lock().unwrap().zones.values().any(|z| z.server == server);
+    if !known {
+        let body = json!({ "error": "Not Found" });
+        return (StatusCode::NOT_FOUND, Json(body)).into_response();
+    }
+    Json(json!({ "id": server, "type": "Server", "daemon_type": "authoritative" })).into_response()
+}
+
+async fn list_zones(State(state): State<Shared>, Path(server): Path<String>) -> Json<Value> {
+    let state = state.lock().unwrap();
+    let stubs: Vec<Value> = state
+        .zones
+        .values()
+        .filter(|z| z.server == server)
+        .map(|z| json!({ "id": z.id, "name": z.name, "account": z.account, "kind": z.kind }))
+        .collect();
+    Json(Value::Array(stubs))
+}
+
+async fn create_zone(
+    State(state): State<Shared>,
+    Path(server): Path<String>,
+    Json(body): Json<Value>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    let name = body["name"].as_str().unwrap_or_default().to_string();
+    let id = state.next_zone_id.take().unwrap_or_else(|| name.clone());
+    let account = body["account"].as_str().unwrap_or_default().to_string();
+    let kind = body["kind"].as_str().unwrap_or_default().to_string();
+    let zone = MockZone {
+        server,
+        id: id.clone(),
+        name: name.clone(),
+        account,
+        kind,
+        metadata: BTreeMap::new(),
+        rrsets: vec![],
+    };
+    state.zones.insert(id.clone(), zone);
+    (
+        StatusCode::CREATED,
+        Json(json!({ "id": id, "name": name, "rrsets": [] })),
+    )
+        .into_response()
+}
+
+async fn get_zone(
+    State(state): State<Shared>,
+    Path((server, zone)): Path<(String, String)>,
+) -> Response {
+    let state = state.lock().unwrap();
+    match state.zones.get(&zone).filter(|z| z.server == server) {
+        Some(z) => Json(json!({
+            "id": z.id,
+            "name": z.name,
+            "account": z.account,
+            "rrsets": z.rrsets,
+        }))
+        .into_response(),
+        None => not_found(),
+    }
+}
+
+async fn patch_zone(
+    State(state): State<Shared>,
+    Path((server, zone)): Path<(String, String)>,
+    Json(body): Json<Value>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    state.patches.push(body.clone());
+    if state.reject_patches.contains(&zone) {
+        let body = json!({ "error": "RRset rejected" });
+        return (StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response();
+    }
+    let Some(z) = state.zones.get_mut(&zone).filter(|z| z.server == server) else {
+        return not_found();
+    };
+
+    // PowerDNS refuses a PATCH that names the same RRset twice, before
+    // applying any of it.
+    let changes = body["rrsets"].as_array().cloned().unwrap_or_default();
+    let mut seen = BTreeSet::new();
+    if let Some(dup) = changes.iter().find(|c| !seen.insert((c["name"].to_string(), c["type"].to_string()))) {
+        let body = json!({ "error": format!("Duplicate RRset {}/{} in request", dup["name"], dup["type"]) });
+        return (StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response();
+    }
+
+    for change in changes {
+        let same = |r: &Value| r["name"] == change["name"] && r["type"] == change["type"];
+        let old_comments = z.rrsets.iter().find(|r| same(r)).and_then(|r| r.get("comments").cloned());
+        z.rrsets.retain(|r| !same(r));
+        if change["changetype"] == "REPLACE" {
+            let mut stored = change.clone();
+            let stored_obj = stored.as_object_mut().unwrap();
+            stored_obj.remove("changetype");
+            // Like PowerDNS, a REPLACE without a comments key keeps the
+            // RRset's existing comments.
+            if !stored_obj.contains_key("comments") {
+                if let Some(comments) = old_comments {
+                    stored_obj.insert("comments".into(), comments);
+                }
+            }
+            z.rrsets.push(stored);
+        }
+    }
+    StatusCode::NO_CONTENT.into_response()
+}
+
+async fn get_metadata(
+    State(state): State<Shared>,
+    Path((server, zone, kind)): Path<(String, String, String)>,
+) -> Response {
+    let state = state.lock().unwrap();
+    match state.zones.get(&zone).filter(|z| z.server == server) {
+        Some(z) => Json(json!({
+            "kind": kind,
+            "metadata": z.metadata.get(&kind).cloned().unwrap_or_default(),
+        }))
+        .into_response(),
+        None => not_found(),
+    }
+}
+
+/// PUT /zones/:id/notify and /rectify: succeed for any known zone unless
+/// `reject_zone_actions` is set.
+async fn zone_action(
+    State(state): State<Shared>,
+    Path((server, zone)): Path<(String, String)>,
+) -> Response {
+    let state = state.lock().unwrap();
+    if state.zones.get(&zone).is_none_or(|z| z.server != server) {
+        return not_found();
+    }
+    if state.reject_zone_actions {
+        let body = json!({ "error": "Domain is not a primary" });
+        return (StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response();
+    }
+    Json(json!({ "result": "ok" })).into_response()
+}
+
+fn not_found() -> Response {
+    (StatusCode::NOT_FOUND, Json(json!({ "error": "Could not find domain" }))).into_response()
+}
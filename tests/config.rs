@@ -0,0 +1,226 @@
+// tests/config.rs
+//
+// Tests for loading and validating `Config` from env-style variables.
+
+use pdns_webhook::config::Config;
+
+fn load(extra: &[(&str, &str)]) -> anyhow::Result<Config> {
+    let mut vars = vec![
+        ("PDNS_API_KEY".to_string(), "secret".to_string()),
+        ("PDNS_API_KEY_FILE".to_string(), "/nonexistent".to_string()),
+    ];
+    vars.extend(extra.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+    Config::from_vars(vars)
+}
+
+/// PDNS_RETRYABLE_STATUSES augments, rather than replaces, 429/5xx.
+#[test]
+fn test_retryable_statuses_augment_defaults() {
+    let cfg = load(&[("PDNS_RETRYABLE_STATUSES", "409, 423")]).unwrap();
+    assert!(cfg.is_retryable_status(409));
+    assert!(cfg.is_retryable_status(423));
+    assert!(cfg.is_retryable_status(429));
+    assert!(cfg.is_retryable_status(503));
+    assert!(!cfg.is_retryable_status(404));
+
+    let cfg = load(&[]).unwrap();
+    assert!(!cfg.is_retryable_status(409));
+}
+
+/// Anything that isn't a status code fails config loading.
+#[test]
+fn test_retryable_statuses_rejects_invalid_codes() {
+    for bad in ["abc", "99", "600", "409,,x"] {
+        let err = load(&[("PDNS_RETRYABLE_STATUSES", bad)]).unwrap_err();
+        assert!(err.to_string().contains("PDNS_RETRYABLE_STATUSES"), "{bad}: {err}");
+    }
+}
+
+/// Domain filter entries match on label boundaries only.
+#[test]
+fn test_domain_filter_respects_label_boundaries() {
+    use pdns_webhook::config::matches_domain_filter;
+
+    let filter = vec!["example.com".to_string()];
+    assert!(matches_domain_filter("example.com", &filter), "apex");
+    assert!(matches_domain_filter("example.com.", &filter), "apex, trailing dot");
+    assert!(matches_domain_filter("sub.example.com", &filter));
+    assert!(matches_domain_filter("a.b.example.com.", &filter));
+    assert!(!matches_domain_filter("notexample.com", &filter));
+    assert!(!matches_domain_filter("evilexample.com", &filter));
+    assert!(!matches_domain_filter("example.com.evil.net", &filter));
+
+    let dotted = vec!["example.com.".to_string()];
+    assert!(matches_domain_filter("sub.example.com", &dotted));
+
+    assert!(matches_domain_filter("anything.org", &[]), "empty filter matches all");
+}
+
+/// TLS needs both the certificate and the key.
+#[test]
+fn test_tls_files_must_be_paired() {
+    assert!(load(&[]).unwrap().tls_files().is_none());
+
+    let cfg = load(&[("TLS_CERT_FILE", "/tls/tls.crt"), ("TLS_KEY_FILE", "/tls/tls.key")]).unwrap();
+    assert_eq!(cfg.tls_files(), Some(("/tls/tls.crt", "/tls/tls.key")));
+
+    let err = load(&[("TLS_CERT_FILE", "/tls/tls.crt")]).unwrap_err();
+    assert!(err.to_string().contains("TLS_KEY_FILE"), "{err}");
+}
+
+/// PDNS_API_KEY_HEADER defaults to X-API-Key and must be a legal header name.
+#[test]
+fn test_api_key_header_validation() {
+    assert_eq!(load(&[]).unwrap().pdns_api_key_header.as_str(), "x-api-key");
+    assert_eq!(load(&[("PDNS_API_KEY_HEADER", "Authorization")]).unwrap().pdns_api_key_header, "authorization");
+
+    let err = load(&[("PDNS_API_KEY_HEADER", "X API Key")]).unwrap_err();
+    assert!(err.to_string().contains("PDNS_API_KEY_HEADER"), "{err}");
+}
+
+/// A path prefix in PDNS_API_URL is kept in front of /api/v1; trailing
+/// slashes and a pasted /api/v1 are not doubled.
+#[test]
+fn test_api_url_keeps_path_prefix() {
+    let zones = |url: &str| format!("{}/zones", load(&[("PDNS_API_URL", url)]).unwrap().server_url("localhost"));
+
+    assert_eq!(zones("https://gw.example.com/powerdns"), "https://gw.example.com/powerdns/api/v1/servers/localhost/zones");
+    assert_eq!(zones("https://gw.example.com/powerdns/"), "https://gw.example.com/powerdns/api/v1/servers/localhost/zones");
+    assert_eq!(zones("https://gw.example.com/powerdns/api/v1"), "https://gw.example.com/powerdns/api/v1/servers/localhost/zones");
+    assert_eq!(zones("http://powerdns:8081"), "http://powerdns:8081/api/v1/servers/localhost/zones");
+
+    for bad in ["powerdns:8081/api", "ftp://powerdns", "http://powerdns:8081/?x=1"] {
+        let err = load(&[("PDNS_API_URL", bad)]).unwrap_err();
+        assert!(err.to_string().contains("PDNS_API_URL"), "{bad}: {err}");
+    }
+}
+
+/// BIND_ADDRESS accepts IPv4 and IPv6 and rejects anything else.
+#[test]
+fn test_bind_address_parsing() {
+    assert_eq!(load(&[]).unwrap().bind_address.to_string(), "0.0.0.0");
+    assert_eq!(load(&[("BIND_ADDRESS", "127.0.0.1")]).unwrap().bind_address.to_string(), "127.0.0.1");
+    assert_eq!(load(&[("BIND_ADDRESS", "::")]).unwrap().bind_address.to_string(), "::");
+
+    let err = load(&[("BIND_ADDRESS", "localhost")]).unwrap_err();
+    assert!(err.to_string().contains("BIND_ADDRESS"), "{err}");
+}
+
+/// MAX_BODY_BYTES defaults to 8 MiB and must allow some body.
+#[test]
+fn test_max_body_bytes() {
+    assert_eq!(load(&[]).unwrap().max_body_bytes, 8 * 1024 * 1024);
+    assert_eq!(load(&[("MAX_BODY_BYTES", "1024")]).unwrap().max_body_bytes, 1024);
+    assert_eq!(load(&[]).unwrap().log_body_max_bytes, 64 * 1024);
+    assert_eq!(load(&[("LOG_BODY_MAX_BYTES", "0")]).unwrap().log_body_max_bytes, 0);
+
+    let err = load(&[("MAX_BODY_BYTES", "0")]).unwrap_err();
+    assert!(err.to_string().contains("MAX_BODY_BYTES"), "{err}");
+}
+
+/// A zero PowerDNS timeout would fail every request at once, so it is
+/// refused rather than passed to reqwest.
+#[test]
+fn test_pdns_timeouts_must_be_positive() {
+    let cfg = load(&[("PDNS_REQUEST_TIMEOUT_SECS", "10"), ("PDNS_CONNECT_TIMEOUT_SECS", "2")]).unwrap();
+    assert_eq!((cfg.pdns_request_timeout_secs, cfg.pdns_connect_timeout_secs), (10, 2));
+
+    for var in ["PDNS_REQUEST_TIMEOUT_SECS", "PDNS_CONNECT_TIMEOUT_SECS"] {
+        let err = load(&[(var, "0")]).unwrap_err();
+        assert!(err.to_string().contains(var), "{err}");
+    }
+}
+
+/// The circuit breaker settings also answer to their shorter names.
+#[test]
+fn test_circuit_breaker_aliases() {
+    let cfg = load(&[("PDNS_CB_THRESHOLD", "3"), ("PDNS_CB_COOLDOWN_SECS", "10")]).unwrap();
+    assert_eq!(cfg.pdns_cb_failure_threshold, 3);
+    assert_eq!(cfg.pdns_cb_cooldown_seconds, 10);
+}
+
+/// The connection pool keeps reqwest's defaults unless configured.
+#[test]
+fn test_pool_settings() {
+    let cfg = load(&[]).unwrap();
+    assert_eq!(cfg.pdns_pool_max_idle_per_host, usize::MAX);
+    assert_eq!(cfg.pdns_pool_idle_timeout_secs, 90);
+
+    let cfg = load(&[("PDNS_POOL_MAX_IDLE_PER_HOST", "4"), ("PDNS_POOL_IDLE_TIMEOUT_SECS", "0")]).unwrap();
+    assert_eq!(cfg.pdns_pool_max_idle_per_host, 4);
+    assert_eq!(cfg.pdns_pool_idle_timeout_secs, 0);
+}
+
+/// EXCLUDE_DOMAINS removes subdomains from an otherwise included zone.
+#[test]
+fn test_exclude_domains_override_include() {
+    let cfg = load(&[
+        ("DOMAIN_FILTER", "example.com"),
+        ("EXCLUDE_DOMAINS", "internal.example.com, "),
+    ])
+    .unwrap();
+    assert_eq!(cfg.exclude_domains_list(), ["internal.example.com"]);
+    assert!(cfg.in_domain_filter("www.example.com"));
+    assert!(cfg.in_domain_filter("notinternal.example.com"));
+    assert!(!cfg.in_domain_filter("internal.example.com"));
+    assert!(!cfg.in_domain_filter("db.internal.example.com"));
+}
+
+/// The regex filters combine include and exclusion; a bad pattern fails
+/// config loading.
+#[test]
+fn test_domain_filter_regex() {
+    let cfg = load(&[
+        ("DOMAIN_FILTER_REGEX", r"^(app|api)\d*\.(example\.com|example\.org)$"),
+        ("DOMAIN_FILTER_REGEX_EXCLUSION", r"^api2\."),
+    ])
+    .unwrap();
+    assert!(cfg.in_domain_filter("app.example.com"));
+    assert!(cfg.in_domain_filter("api1.example.org."));
+    assert!(!cfg.in_domain_filter("api2.example.org"));
+    assert!(!cfg.in_domain_filter("www.example.com"));
+
+    let err = load(&[("DOMAIN_FILTER_REGEX", "([a-z")]).unwrap_err();
+    assert!(err.to_string().contains("DOMAIN_FILTER_REGEX"), "{err}");
+}
+
+/// TTLs are clamped into MIN_TTL..=MAX_TTL; unset bounds don't clamp.
+#[test]
+fn test_clamp_ttl_bounds() {
+    let cfg = load(&[("MIN_TTL", "60"), ("MAX_TTL", "3600")]).unwrap();
+    assert_eq!(cfg.clamp_ttl(1), 60);
+    assert_eq!(cfg.clamp_ttl(59), 60);
+    assert_eq!(cfg.clamp_ttl(60), 60);
+    assert_eq!(cfg.clamp_ttl(3600), 3600);
+    assert_eq!(cfg.clamp_ttl(3601), 3600);
+
+    let cfg = load(&[]).unwrap();
+    assert_eq!(cfg.clamp_ttl(1), 1);
+    assert_eq!(cfg.clamp_ttl(u32::MAX), u32::MAX);
+
+    let err = load(&[("MIN_TTL", "600"), ("MAX_TTL", "60")]).unwrap_err();
+    assert!(err.to_string().contains("MIN_TTL"), "{err}");
+}
+
+/// AUTO_CREATE_ZONES names the longest DOMAIN_FILTER entry at or above the
+/// record, and needs a domain filter to be set at all.
+#[test]
+fn test_auto_create_zone_for() {
+    let cfg = load(&[
+        ("AUTO_CREATE_ZONES", "true"),
+        ("DOMAIN_FILTER", "example.com, apps.example.com"),
+        ("EXCLUDE_DOMAINS", "internal.example.com"),
+    ])
+    .unwrap();
+    assert_eq!(cfg.auto_create_zone_for("www.example.com").as_deref(), Some("example.com"));
+    assert_eq!(cfg.auto_create_zone_for("a.apps.example.com.").as_deref(), Some("apps.example.com"));
+    assert_eq!(cfg.auto_create_zone_for("apps.example.com").as_deref(), Some("apps.example.com"));
+    assert_eq!(cfg.auto_create_zone_for("a.internal.example.com"), None);
+    assert_eq!(cfg.auto_create_zone_for("www.example.org"), None);
+
+    let cfg = load(&[("DOMAIN_FILTER", "example.com")]).unwrap();
+    assert_eq!(cfg.auto_create_zone_for("www.example.com"), None);
+
+    let err = load(&[("AUTO_CREATE_ZONES", "true")]).unwrap_err();
+    assert!(err.to_string().contains("DOMAIN_FILTER"), "{err}");
+}
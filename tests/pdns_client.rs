@@ -0,0 +1,235 @@
+// tests/pdns_client.rs
+//
+// Integration tests that drive `PdnsClient` directly against the in-process
+// mock PowerDNS from tests/common.
+//
+// Run:
+//   cargo test --test pdns_client
+
+mod common;
+
+use common::MockPdns;
+use pdns_webhook::{
+    config::Config,
+    pdns::{PdnsClient, PdnsError, ZoneRef},
+};
+
+/// The id PowerDNS assigns to a new zone is used as-is, even when it isn't
+/// the zone name.
+#[tokio::test]
+async fn test_create_zone_returns_assigned_id() {
+    let pdns = MockPdns::start().await;
+    pdns.state.lock().unwrap().next_zone_id = Some("=2Enew-zone".into());
+
+    let client = PdnsClient::new(pdns.config(&[])).unwrap();
+    let id = client.create_zone("new.example.com", "Native").await.unwrap();
+
+    assert_eq!(id, "=2Enew-zone");
+    assert!(pdns.state.lock().unwrap().zones.contains_key("=2Enew-zone"));
+}
+
+/// A client certificate that can't be loaded fails construction instead of
+/// silently connecting without it.
+#[tokio::test]
+async fn test_client_certificate_errors_surface_at_startup() {
+    let pdns = MockPdns::start().await;
+
+    let missing = pdns.config(&[
+        ("PDNS_CLIENT_CERT_FILE", "/nonexistent/client.crt"),
+        ("PDNS_CLIENT_KEY_FILE", "/nonexistent/client.key"),
+    ]);
+    let err = PdnsClient::new(missing).err().unwrap();
+    assert!(format!("{err:#}").contains("/nonexistent/client.crt"), "{err:#}");
+
+    let dir = std::env::temp_dir().join(format!("pdns-webhook-mtls-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let garbage = dir.join("garbage.pem");
+    std::fs::write(&garbage, "not a certificate").unwrap();
+    let garbage = garbage.to_str().unwrap();
+
+    let invalid = pdns.config(&[
+        ("PDNS_CLIENT_CERT_FILE", garbage),
+        ("PDNS_CLIENT_KEY_FILE", garbage),
+    ]);
+    let err = PdnsClient::new(invalid).err().unwrap();
+    assert!(err.to_string().contains("client certificate"), "{err:#}");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// The readiness ping succeeds against a reachable PowerDNS.
+#[tokio::test]
+async fn test_ping_reaches_server_object() {
+    let pdns = MockPdns::start().await;
+    let client = PdnsClient::new(pdns.config(&[])).unwrap();
+
+    client.ping().await.unwrap();
+    assert_eq!(pdns.count("GET /api/v1/servers/localhost"), 1);
+}
+
+/// The startup check passes against a correctly configured PowerDNS and
+/// names the setting to fix when it isn't.
+#[tokio::test]
+async fn test_check_server_reports_misconfiguration() {
+    let pdns = MockPdns::start().await;
+    PdnsClient::new(pdns.config(&[])).unwrap().check_server().await.unwrap();
+
+    let wrong_id = PdnsClient::new(pdns.config(&[("PDNS_SERVER_ID", "nope")])).unwrap();
+    let err = wrong_id.check_server().await.unwrap_err();
+    assert!(format!("{err:#}").contains("PDNS_SERVER_ID"), "{err:#}");
+
+    pdns.fail_next(&[401]);
+    let err = PdnsClient::new(pdns.config(&[])).unwrap().check_server().await.unwrap_err();
+    assert!(format!("{err:#}").contains("PDNS_API_KEY"), "{err:#}");
+
+    let cfg = pdns.config(&[("PDNS_API_URL", "http://127.0.0.1:1"), ("PDNS_MAX_RETRIES", "0")]);
+    let err = PdnsClient::new(cfg).unwrap().check_server().await.unwrap_err();
+    assert!(format!("{err:#}").contains("PDNS_API_URL"), "{err:#}");
+}
+
+/// A rotated key file is picked up by `reload_api_key`; an unreadable one
+/// keeps the current key, and an inline key is never reloaded.
+#[tokio::test]
+async fn test_api_key_reloads_from_file() {
+    let pdns = MockPdns::start().await;
+    let dir = std::env::temp_dir().join(format!("pdns-webhook-key-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let key_file = dir.join("api-key");
+    std::fs::write(&key_file, "first\n").unwrap();
+
+    let client =
+        PdnsClient::new(pdns.config(&[("PDNS_API_KEY_FILE", key_file.to_str().unwrap())])).unwrap();
+    client.ping().await.unwrap();
+
+    std::fs::write(&key_file, "second\n").unwrap();
+    assert!(client.reload_api_key().unwrap());
+    assert!(!client.reload_api_key().unwrap());
+    client.ping().await.unwrap();
+
+    std::fs::write(&key_file, "").unwrap();
+    assert!(client.reload_api_key().is_err());
+    client.ping().await.unwrap();
+    assert_eq!(pdns.header("X-API-Key"), ["first", "second", "second"]);
+
+    let inline = PdnsClient::new(pdns.config(&[])).unwrap();
+    assert!(!inline.reload_api_key().unwrap());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// PDNS_API_KEY_HEADER renames the header the key is sent in.
+#[tokio::test]
+async fn test_api_key_header_is_configurable() {
+    let pdns = MockPdns::start().await;
+    let client = PdnsClient::new(pdns.config(&[("PDNS_API_KEY_HEADER", "X-Gateway-Key")])).unwrap();
+    client.ping().await.unwrap();
+
+    assert_eq!(pdns.header("X-Gateway-Key"), ["secret"]);
+    assert_eq!(pdns.header("X-API-Key"), [""]);
+}
+
+/// The API key is masked wherever it appears in text bound for the logs.
+#[tokio::test]
+async fn test_redact_masks_api_key() {
+    let pdns = MockPdns::start().await;
+    let client = PdnsClient::new(pdns.config(&[])).unwrap();
+
+    assert_eq!(client.redact(r#"{"key":"secret","again":"secret"}"#), r#"{"key":"***","again":"***"}"#);
+    assert_eq!(client.redact("nothing to hide"), "nothing to hide");
+}
+
+/// Transient 5xx responses are retried until PowerDNS recovers; a 4xx is
+/// returned at once.
+#[tokio::test]
+async fn test_transient_failures_are_retried() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    let client = PdnsClient::new(pdns.config(&[("PDNS_RETRY_BASE_MS", "1")])).unwrap();
+
+    pdns.fail_next(&[503, 502]);
+    let zones = client.list_zones().await.unwrap();
+    assert_eq!(zones.len(), 1);
+    assert_eq!(pdns.count("GET /api/v1/servers/localhost/zones"), 3);
+
+    pdns.fail_next(&[400]);
+    let zone = ZoneRef { server: "localhost".into(), id: "example.com.".into() };
+    assert!(client.get_zone(&zone).await.is_err());
+    assert_eq!(pdns.count("GET /api/v1/servers/localhost/zones/example.com."), 1);
+}
+
+/// Retries stop after PDNS_MAX_RETRIES and the last failure is reported.
+#[tokio::test]
+async fn test_retries_are_bounded() {
+    let pdns = MockPdns::start().await;
+    let cfg = pdns.config(&[("PDNS_RETRY_BASE_MS", "1"), ("PDNS_MAX_RETRIES", "2")]);
+    let client = PdnsClient::new(cfg).unwrap();
+
+    pdns.fail_next(&[503, 503, 503, 503]);
+    let err = client.list_zones().await.unwrap_err();
+    assert!(err.to_string().contains("503"), "{err}");
+    assert_eq!(pdns.count("GET /api/v1/servers/localhost/zones"), 3);
+}
+
+/// A PowerDNS that doesn't answer in time fails with an error naming the URL
+/// instead of hanging.
+#[tokio::test]
+async fn test_request_timeout_names_url() {
+    let pdns = MockPdns::start().await;
+    pdns.state.lock().unwrap().delay = Some(std::time::Duration::from_secs(5));
+    let cfg = pdns.config(&[("PDNS_REQUEST_TIMEOUT_SECS", "1"), ("PDNS_MAX_RETRIES", "0")]);
+    let client = PdnsClient::new(cfg).unwrap();
+
+    let err = client.list_zones().await.unwrap_err();
+    let expected = format!("PowerDNS at {}/api/v1/servers/localhost/zones timed out", pdns.url);
+    assert!(err.to_string().contains(&expected), "{err:#}");
+}
+
+/// PDNS_HTTP2 works against an endpoint that speaks HTTP/2 (the mock does),
+/// and against one that only speaks HTTP/1.1 the failure names the setting.
+#[tokio::test]
+async fn test_http2_prior_knowledge() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    let cfg = pdns.config(&[("PDNS_HTTP2", "true"), ("PDNS_MAX_RETRIES", "0")]);
+    assert_eq!(PdnsClient::new(cfg.clone()).unwrap().list_zones().await.unwrap().len(), 1);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http1_only = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        use tokio::io::AsyncWriteExt;
+        while let Ok((mut conn, _)) = listener.accept().await {
+            let _ = conn.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n").await;
+        }
+    });
+    let client = PdnsClient::new(Config { pdns_api_url: http1_only, ..cfg.clone() }).unwrap();
+    let err = client.list_zones().await.unwrap_err();
+    assert!(err.to_string().contains("PDNS_HTTP2 is set"), "{err:#}");
+    assert_eq!(err.status(), 502);
+
+    // A PowerDNS that is simply down is not blamed on HTTP/2.
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let down = format!("http://{}", closed.local_addr().unwrap());
+    drop(closed);
+    let client = PdnsClient::new(Config { pdns_api_url: down, ..cfg }).unwrap();
+    let err = client.list_zones().await.unwrap_err();
+    assert!(matches!(err, PdnsError::Connection { .. }), "{err:#}");
+    assert_eq!(err.status(), 502);
+}
+
+/// VALIDATE_DOMAIN_FILTER accepts filters at, above or below a zone and
+/// lists the ones that match nothing.
+#[tokio::test]
+async fn test_check_domain_filter_lists_unmatched_entries() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    pdns.add_zone("team.example.org", vec![]);
+
+    let ok = pdns.config(&[("DOMAIN_FILTER", "example.com,app.example.com,example.org")]);
+    PdnsClient::new(ok).unwrap().check_domain_filter().await.unwrap();
+
+    let typo = pdns.config(&[("DOMAIN_FILTER", "example.com,exmaple.com,other.net")]);
+    let err = PdnsClient::new(typo).unwrap().check_domain_filter().await.unwrap_err();
+    assert_eq!(err.to_string(), "DOMAIN_FILTER entries match no PowerDNS zone: exmaple.com, other.net");
+
+    PdnsClient::new(pdns.config(&[])).unwrap().check_domain_filter().await.unwrap();
+}
@@ -0,0 +1,181 @@
+// tests/provider.rs
+//
+// Handler tests against an in-memory `DnsProvider`, with no PowerDNS (real
+// or mocked) involved.
+//
+// Run:
+//   cargo test --test provider
+
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    routing::{get, post},
+    Router,
+};
+use pdns_webhook::{
+    config::{ApplyMode, Config},
+    dns::{Changes, Endpoint},
+    handlers,
+    pdns::{PdnsError, ZoneCache, ZonePatch},
+    provider::DnsProvider,
+    AppState,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt; // for `.oneshot()`
+
+type Result<T> = std::result::Result<T, PdnsError>;
+
+/// Serves a fixed endpoint list, plans one empty patch per endpoint name and
+/// records which patches were applied.  Patches for `fail_zone` fail.
+///
+/// With `shared_zone`, every endpoint instead lands in zone `example.com.`
+/// on the server named by its first label, and patches to `fail_server`
+/// fail.
+#[derive(Default)]
+struct FakeProvider {
+    endpoints: Vec<Endpoint>,
+    fail_zone: Option<String>,
+    shared_zone: bool,
+    fail_server: Option<String>,
+    applied: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait]
+impl DnsProvider for FakeProvider {
+    async fn list_endpoints(&self, _: &[String], _: &[String]) -> Result<Vec<Endpoint>> {
+        Ok(self.endpoints.clone())
+    }
+
+    async fn plan_changes(&self, changes: &Changes, _: u32, _: &ZoneCache) -> Result<Vec<ZonePatch>> {
+        Ok(changes
+            .create
+            .iter()
+            .map(|ep| match self.shared_zone {
+                true => {
+                    let server = ep.dns_name.split('.').next().unwrap_or_default().to_string();
+                    ZonePatch { server, zone: "example.com.".into(), rrsets: vec![] }
+                }
+                false => ZonePatch { server: "localhost".into(), zone: ep.dns_name.clone(), rrsets: vec![] },
+            })
+            .collect())
+    }
+
+    async fn apply_patch(&self, patch: &ZonePatch) -> Result<()> {
+        if self.fail_zone.as_deref() == Some(patch.zone.as_str())
+            || self.fail_server.as_deref() == Some(patch.server.as_str())
+        {
+            let what = format!("PATCH {}", patch.zone);
+            return Err(PdnsError::Upstream { what, status: 500, body: "broken".into() });
+        }
+        self.applied.lock().unwrap().push(patch.zone.clone());
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn config() -> Config {
+    Config::from_vars([
+        ("PDNS_API_KEY".to_string(), "secret".to_string()),
+        ("PDNS_API_KEY_FILE".to_string(), "/nonexistent".to_string()),
+    ])
+    .unwrap()
+}
+
+fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/records", get(handlers::get_records))
+        .route("/records", post(handlers::apply_changes))
+        .route("/readyz",  get(handlers::readyz))
+        .with_state(state)
+}
+
+async fn send(app: Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn test_get_records_serves_provider_endpoints() {
+    let provider = FakeProvider {
+        endpoints: vec![Endpoint {
+            dns_name: "www.example.com".into(),
+            record_type: "A".into(),
+            targets: vec!["10.0.0.1".into()],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let (status, body) = send(router(AppState::new(config(), provider)), "GET", "/records", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body[0]["dnsName"], "www.example.com");
+    assert_eq!(body[0]["targets"], json!(["10.0.0.1"]));
+}
+
+#[tokio::test]
+async fn test_apply_stops_at_first_failing_patch() {
+    let provider = FakeProvider { fail_zone: Some("b.example.com".into()), ..Default::default() };
+    let applied = provider.applied.clone();
+    let state = AppState::new(config(), provider);
+
+    let changes = json!({
+        "create": [
+            { "dnsName": "a.example.com", "recordType": "A", "targets": ["10.0.0.1"] },
+            { "dnsName": "b.example.com", "recordType": "A", "targets": ["10.0.0.2"] },
+            { "dnsName": "c.example.com", "recordType": "A", "targets": ["10.0.0.3"] },
+        ],
+    });
+    let (status, body) = send(router(state), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert!(body["error"].as_str().unwrap().contains("b.example.com"));
+    assert_eq!(*applied.lock().unwrap(), ["a.example.com"]);
+}
+
+/// In continue mode a failed PATCH is blamed only on the endpoints planned
+/// for that server's zone, not on those in a same-named zone elsewhere.
+#[tokio::test]
+async fn test_continue_blames_failures_per_server() {
+    let provider = FakeProvider { shared_zone: true, fail_server: Some("b".into()), ..Default::default() };
+    let applied = provider.applied.clone();
+    let cfg = Config { apply_mode: ApplyMode::Continue, ..config() };
+    let state = AppState::new(cfg, provider);
+
+    let changes = json!({
+        "create": [
+            { "dnsName": "a.example.com", "recordType": "A", "targets": ["10.0.0.1"] },
+            { "dnsName": "b.example.com", "recordType": "A", "targets": ["10.0.0.2"] },
+        ],
+    });
+    let (status, body) = send(router(state), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(body["applied"], 0);
+    let failed: Vec<&str> = body["failed"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| f["dnsName"].as_str().unwrap())
+        .collect();
+    assert_eq!(failed, ["b.example.com"]);
+    assert_eq!(*applied.lock().unwrap(), ["example.com."]);
+}
+
+#[tokio::test]
+async fn test_readyz_uses_provider_ping() {
+    let (status, body) = send(router(AppState::new(config(), FakeProvider::default())), "GET", "/readyz", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["circuit"], "closed");
+}
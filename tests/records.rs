@@ -0,0 +1,1862 @@
+// tests/records.rs
+//
+// Integration tests for GET /, GET/POST /records, POST /records/preview and
+// the debug GET /zones, run against the in-process mock PowerDNS from
+// tests/common.
+//
+// Run:
+//   cargo test --test records
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use common::{rrset, MockPdns};
+use pdns_webhook::{config::Config, handlers, pdns::PdnsClient, AppState};
+use serde_json::{json, Value};
+use tower::ServiceExt; // for `.oneshot()`
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Helpers
+// ─────────────────────────────────────────────────────────────────────────────
+
+fn test_router(cfg: Config) -> Router {
+    let pdns = PdnsClient::new(cfg.clone()).unwrap();
+    Router::new()
+        .route("/",                get(handlers::negotiate))
+        .route("/records",         get(handlers::get_records))
+        .route("/records",         post(handlers::apply_changes))
+        .route("/records/preview", post(handlers::preview_changes))
+        .route("/zones",           get(handlers::debug_zones))
+        .route("/status",          get(handlers::status))
+        .with_state(AppState::new(cfg, pdns))
+}
+
+/// Send a request and return the status plus parsed JSON body (Null if empty).
+async fn send(app: Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+fn endpoint(name: &str, rtype: &str, targets: &[&str]) -> Value {
+    json!({ "dnsName": name, "recordType": rtype, "targets": targets })
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Tests
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Preview groups RRsets per zone, normalises content, and writes nothing.
+#[tokio::test]
+async fn test_preview_returns_plan_without_patching() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![rrset("old.example.com", "A", 300, &["10.0.0.1"])]);
+    pdns.add_zone("example.net", vec![]);
+
+    let changes = json!({
+        "create": [
+            endpoint("www.example.com", "CNAME", &["lb.example.net"]),
+            endpoint("api.example.net", "A", &["10.0.0.2"]),
+        ],
+        "delete": [endpoint("old.example.com", "A", &["10.0.0.1"])],
+    });
+
+    let app = test_router(pdns.config(&[]));
+    let (status, body) = send(app, "POST", "/records/preview", Some(changes)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    let zones = body["zones"].as_array().unwrap();
+    assert_eq!(zones.len(), 2);
+
+    assert_eq!(zones[0]["zone"], "example.com.");
+    let rrsets = zones[0]["rrsets"].as_array().unwrap();
+    assert_eq!(rrsets[0]["changetype"], "DELETE");
+    assert_eq!(rrsets[0]["name"], "old.example.com.");
+    assert_eq!(rrsets[1]["changetype"], "REPLACE");
+    assert_eq!(rrsets[1]["records"][0]["content"], "lb.example.net.");
+
+    assert_eq!(zones[1]["zone"], "example.net.");
+    assert_eq!(zones[1]["rrsets"][0]["records"][0]["content"], "10.0.0.2");
+
+    assert!(pdns.patches().is_empty(), "preview must not PATCH");
+}
+
+/// With SKIP_OUT_OF_FILTER_CHANGES=false, changes for names outside
+/// DOMAIN_FILTER are rejected before anything is written.
+#[tokio::test]
+async fn test_apply_rejects_changes_outside_domain_filter_when_configured() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    pdns.add_zone("notexample.com", vec![]);
+
+    let changes = json!({
+        "create": [
+            endpoint("www.example.com", "A", &["10.0.0.1"]),
+            endpoint("www.notexample.com", "A", &["10.0.0.2"]),
+        ],
+    });
+
+    let cfg = pdns.config(&[
+        ("DOMAIN_FILTER", "example.com"),
+        ("SKIP_OUT_OF_FILTER_CHANGES", "false"),
+    ]);
+    let (status, body) = send(test_router(cfg), "POST", "/records", Some(changes)).await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(body["error"].as_str().unwrap().contains("www.notexample.com"));
+    assert!(pdns.patches().is_empty());
+}
+
+/// By default changes for names outside DOMAIN_FILTER are skipped with a
+/// warning and the in-scope ones still apply.
+#[tokio::test]
+async fn test_apply_skips_changes_outside_domain_filter() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    pdns.add_zone("notexample.com", vec![]);
+
+    let changes = json!({
+        "create": [
+            endpoint("www.example.com", "A", &["10.0.0.1"]),
+            endpoint("www.notexample.com", "A", &["10.0.0.2"]),
+        ],
+    });
+
+    let cfg = pdns.config(&[("DOMAIN_FILTER", "example.com")]);
+    let (status, _) = send(test_router(cfg), "POST", "/records", Some(changes)).await;
+
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(pdns.rrsets("example.com").len(), 1);
+    assert!(pdns.rrsets("notexample.com").is_empty());
+    assert_eq!(pdns.count("PATCH /api/v1/servers/localhost/zones/example.com."), 1);
+    assert_eq!(pdns.count("PATCH /api/v1/servers/localhost/zones/notexample.com."), 0);
+}
+
+/// Two A endpoints for the same name end up as one RRset with both targets
+/// rather than the second REPLACE clobbering the first.
+#[tokio::test]
+async fn test_apply_merges_same_name_endpoints() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+
+    let changes = json!({
+        "create": [
+            endpoint("www.example.com", "A", &["10.0.0.1"]),
+            endpoint("www.example.com", "A", &["10.0.0.2"]),
+        ],
+    });
+
+    let (status, _) = send(test_router(pdns.config(&[])), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let rrsets = pdns.rrsets("example.com");
+    assert_eq!(rrsets.len(), 1);
+    let contents: Vec<&str> = rrsets[0]["records"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["content"].as_str().unwrap())
+        .collect();
+    assert_eq!(contents, ["10.0.0.1", "10.0.0.2"]);
+}
+
+/// With AUDIT_INTENT_COMMENTS the create/update intent is carried as an
+/// RRset comment while the changetype stays REPLACE.
+#[tokio::test]
+async fn test_preview_tags_intent_comments_when_enabled() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+
+    let changes = json!({
+        "create":     [endpoint("new.example.com", "A", &["10.0.0.1"])],
+        "updateNew":  [endpoint("old.example.com", "A", &["10.0.0.2"])],
+    });
+
+    let cfg = pdns.config(&[("AUDIT_INTENT_COMMENTS", "true")]);
+    let (_, body) = send(test_router(cfg), "POST", "/records/preview", Some(changes.clone())).await;
+    let rrsets = &body["zones"][0]["rrsets"];
+    assert_eq!(rrsets[0]["name"], "old.example.com.");
+    assert_eq!(rrsets[0]["changetype"], "REPLACE");
+    assert_eq!(rrsets[0]["comments"][0]["content"], "external-dns update");
+    assert_eq!(rrsets[1]["comments"][0]["content"], "external-dns create");
+
+    let (_, body) = send(test_router(pdns.config(&[])), "POST", "/records/preview", Some(changes)).await;
+    assert_eq!(body["zones"][0]["rrsets"][0]["comments"], json!([]));
+}
+
+/// Whitespace around targets is stripped before writing; TXT keeps the
+/// whitespace inside its quotes.
+#[tokio::test]
+async fn test_preview_trims_padded_targets() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+
+    let changes = json!({
+        "create": [
+            endpoint("a.example.com",     "A",     &["1.2.3.4 "]),
+            endpoint("cname.example.com", "CNAME", &["  lb.example.com \t"]),
+            endpoint("txt.example.com",   "TXT",   &[" \"v=spf1 -all \" "]),
+        ],
+    });
+
+    let (status, body) = send(test_router(pdns.config(&[])), "POST", "/records/preview", Some(changes)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    let rrsets = &body["zones"][0]["rrsets"];
+    assert_eq!(rrsets[0]["records"][0]["content"], "1.2.3.4");
+    assert_eq!(rrsets[1]["records"][0]["content"], "lb.example.com.");
+    assert_eq!(rrsets[2]["records"][0]["content"], "\"v=spf1 -all \"");
+}
+
+/// TXT values are written quoted and split into 255-byte strings, and read
+/// back unquoted and joined, so a long value round-trips unchanged.
+#[tokio::test]
+async fn test_long_txt_round_trip() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone(
+        "example.com",
+        vec![rrset("split.example.com", "TXT", 300, &["\"v=DKIM1; \" \"p=MIGf\\\"x\""])],
+    );
+    let cfg = pdns.config(&[]);
+
+    let long = format!("v=spf1 {}", "a".repeat(293));
+    let changes = json!({ "create": [endpoint("long.example.com", "TXT", &[&long])] });
+    let (status, _) = send(test_router(cfg.clone()), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let written = pdns.rrsets("example.com")[1]["records"][0]["content"].clone();
+    let expected = format!("\"{}\" \"{}\"", &long[..255], &long[255..]);
+    assert_eq!(written, expected.as_str());
+
+    let (_, first) = send(test_router(cfg.clone()), "GET", "/records", None).await;
+    let txt = |name: &str| first.as_array().unwrap().iter().find(|ep| ep["dnsName"] == name).unwrap().clone();
+    assert_eq!(txt("long.example.com")["targets"], json!([long]));
+    assert_eq!(txt("split.example.com")["targets"], json!(["v=DKIM1; p=MIGf\"x"]));
+
+    let changes = json!({ "updateOld": [txt("long.example.com")], "updateNew": [txt("long.example.com")] });
+    let (status, _) = send(test_router(cfg.clone()), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(pdns.rrsets("example.com")[1]["records"][0]["content"], written);
+
+    let (_, second) = send(test_router(cfg), "GET", "/records", None).await;
+    assert_eq!(first, second);
+}
+
+/// SET_PTR_FOR_FORWARD adds `set-ptr` to A/AAAA records only, and only when
+/// enabled.
+#[tokio::test]
+async fn test_preview_set_ptr_only_when_enabled() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+
+    let changes = json!({
+        "create": [
+            endpoint("a.example.com",     "A",     &["10.0.0.1"]),
+            endpoint("cname.example.com", "CNAME", &["a.example.com"]),
+        ],
+    });
+
+    let cfg = pdns.config(&[("SET_PTR_FOR_FORWARD", "true")]);
+    let (_, body) = send(test_router(cfg), "POST", "/records/preview", Some(changes.clone())).await;
+    let rrsets = &body["zones"][0]["rrsets"];
+    assert_eq!(rrsets[0]["records"][0]["set-ptr"], true);
+    assert!(rrsets[1]["records"][0].get("set-ptr").is_none());
+
+    let (_, body) = send(test_router(pdns.config(&[])), "POST", "/records/preview", Some(changes)).await;
+    assert!(body["zones"][0]["rrsets"][0]["records"][0].get("set-ptr").is_none());
+}
+
+/// A record written without a TTL reads back without one when
+/// OMIT_DEFAULT_TTL_ON_READ is set, and with the stored TTL otherwise.
+#[tokio::test]
+async fn test_default_ttl_round_trip_omitted_on_read() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![rrset("manual.example.com", "A", 3600, &["10.0.0.9"])]);
+
+    let changes = json!({ "create": [endpoint("www.example.com", "A", &["10.0.0.1"])] });
+    let cfg = pdns.config(&[("OMIT_DEFAULT_TTL_ON_READ", "true")]);
+    let (status, _) = send(test_router(cfg.clone()), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(pdns.rrsets("example.com")[1]["ttl"], 300);
+
+    let (_, body) = send(test_router(cfg), "GET", "/records", None).await;
+    let ttl_of = |body: &Value, name: &str| {
+        body.as_array()
+            .unwrap()
+            .iter()
+            .find(|ep| ep["dnsName"] == name)
+            .map(|ep| ep["recordTTL"].clone())
+            .unwrap()
+    };
+    assert_eq!(ttl_of(&body, "www.example.com"), 0);
+    assert_eq!(ttl_of(&body, "manual.example.com"), 3600);
+
+    let (_, body) = send(test_router(pdns.config(&[])), "GET", "/records", None).await;
+    assert_eq!(ttl_of(&body, "www.example.com"), 300);
+}
+
+/// PDNS_ACCOUNT_FILTER hides zones owned by other accounts from reads and
+/// keeps writes out of them.
+#[tokio::test]
+async fn test_account_filter_limits_managed_zones() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("ours.example", vec![rrset("www.ours.example", "A", 300, &["10.0.0.1"])]);
+    pdns.add_zone("theirs.example", vec![rrset("www.theirs.example", "A", 300, &["10.0.0.2"])]);
+    pdns.set_account("ours.example", "team-a");
+    pdns.set_account("theirs.example", "team-b");
+
+    let cfg = pdns.config(&[("PDNS_ACCOUNT_FILTER", "team-a")]);
+    let (_, body) = send(test_router(cfg.clone()), "GET", "/records", None).await;
+    let names: Vec<&str> = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|ep| ep["dnsName"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["www.ours.example"]);
+
+    let changes = json!({ "create": [endpoint("new.theirs.example", "A", &["10.0.0.3"])] });
+    let (status, body) = send(test_router(cfg), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(body["error"].as_str().unwrap().starts_with("no PowerDNS zone found for new.theirs.example"));
+    assert!(pdns.patches().is_empty());
+}
+
+/// A name under another account's child zone is refused, not written into
+/// our parent zone where that delegation would hide it.
+#[tokio::test]
+async fn test_other_accounts_child_zone_is_not_bypassed() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    pdns.add_zone("team.example.com", vec![]);
+    pdns.set_account("example.com", "team-a");
+    pdns.set_account("team.example.com", "team-b");
+
+    let cfg = pdns.config(&[("PDNS_ACCOUNT_FILTER", "team-a")]);
+    let changes = json!({ "create": [endpoint("www.team.example.com", "A", &["10.0.0.3"])] });
+    let (status, body) = send(test_router(cfg), "POST", "/records", Some(changes)).await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(body["error"].as_str().unwrap().contains("account 'team-b'"), "{body}");
+    assert!(pdns.patches().is_empty());
+}
+
+/// A zone flagged via ZONE_SUSPEND_METADATA_KIND is neither reported nor
+/// written to.
+#[tokio::test]
+async fn test_suspended_zone_is_skipped_and_read_only() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("live.example", vec![rrset("www.live.example", "A", 300, &["10.0.0.1"])]);
+    pdns.add_zone("frozen.example", vec![rrset("www.frozen.example", "A", 300, &["10.0.0.2"])]);
+    pdns.set_metadata("frozen.example", "X-EXTERNAL-DNS-SUSPENDED", &["1"]);
+
+    let cfg = pdns.config(&[("ZONE_SUSPEND_METADATA_KIND", "X-EXTERNAL-DNS-SUSPENDED")]);
+    let (_, body) = send(test_router(cfg.clone()), "GET", "/records", None).await;
+    let names: Vec<&str> = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|ep| ep["dnsName"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["www.live.example"]);
+
+    let changes = json!({ "create": [endpoint("new.frozen.example", "A", &["10.0.0.3"])] });
+    let (status, body) = send(test_router(cfg), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(body["error"].as_str().unwrap().contains("suspended"));
+    assert!(pdns.patches().is_empty());
+}
+
+/// The suspension metadata is read once per zone per apply, not once per
+/// endpoint.
+#[tokio::test]
+async fn test_suspension_is_checked_once_per_zone() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    let cfg = pdns.config(&[("ZONE_SUSPEND_METADATA_KIND", "X-EXTERNAL-DNS-SUSPENDED")]);
+
+    let changes = json!({ "create": [
+        endpoint("a.example.com", "A", &["10.0.0.1"]),
+        endpoint("b.example.com", "A", &["10.0.0.2"]),
+        endpoint("c.example.com", "TXT", &["hello"]),
+    ] });
+    let (status, _) = send(test_router(cfg), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let meta = "GET /api/v1/servers/localhost/zones/example.com./metadata/X-EXTERNAL-DNS-SUSPENDED";
+    assert_eq!(pdns.count(meta), 1);
+}
+
+/// Zone resolution lists zones once per apply, however many endpoints and
+/// labels are involved, and each zone's contents are read at most once.
+#[tokio::test]
+async fn test_apply_lists_zones_once_per_request() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![rrset("old.example.com", "A", 300, &["10.0.0.9"])]);
+
+    let changes = json!({
+        "create": [
+            endpoint("a.example.com", "A", &["10.0.0.1"]),
+            endpoint("b.example.com", "A", &["10.0.0.2"]),
+            endpoint("c.d.e.f.example.com", "A", &["10.0.0.3"]),
+        ],
+        "delete": [endpoint("old.example.com", "A", &["10.0.0.9"])],
+    });
+
+    let (status, _) = send(test_router(pdns.config(&[])), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(pdns.count("GET /api/v1/servers/localhost/zones"), 1);
+    assert_eq!(pdns.count("GET /api/v1/servers/localhost/zones/example.com."), 1);
+
+    // A second request starts with an empty cache.
+    let changes = json!({ "create": [endpoint("d.example.com", "A", &["10.0.0.4"])] });
+    send(test_router(pdns.config(&[])), "POST", "/records", Some(changes)).await;
+    assert_eq!(pdns.count("GET /api/v1/servers/localhost/zones"), 2);
+}
+
+/// The zone list is reused across requests until ZONE_CACHE_TTL expires or
+/// a write invalidates it.
+#[tokio::test]
+async fn test_zone_list_cached_across_requests() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    let list = "GET /api/v1/servers/localhost/zones";
+
+    let app = test_router(pdns.config(&[]));
+    send(app.clone(), "GET", "/records", None).await;
+    send(app.clone(), "GET", "/records", None).await;
+    assert_eq!(pdns.count(list), 1);
+
+    let changes = json!({ "create": [endpoint("www.example.com", "A", &["10.0.0.1"])] });
+    let (status, _) = send(app.clone(), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(pdns.count(list), 1);
+
+    // The PATCH dropped the cached list.
+    send(app, "GET", "/records", None).await;
+    assert_eq!(pdns.count(list), 2);
+
+    let app = test_router(pdns.config(&[("ZONE_CACHE_TTL", "0")]));
+    send(app.clone(), "GET", "/records", None).await;
+    send(app, "GET", "/records", None).await;
+    assert_eq!(pdns.count(list), 4);
+}
+
+/// With APPLY_MODE=atomic a failed PATCH rolls back zones already patched
+/// in the batch; best-effort leaves them changed.
+#[tokio::test]
+async fn test_atomic_apply_rolls_back_earlier_zones() {
+    let changes = json!({
+        "create":    [endpoint("new.example.com", "A", &["10.0.0.3"]),
+                      endpoint("www.example.net", "A", &["10.0.0.4"])],
+        "updateOld": [endpoint("www.example.com", "A", &["10.0.0.1"])],
+        "updateNew": [endpoint("www.example.com", "A", &["10.0.0.2"])],
+    });
+    let original = vec![rrset("www.example.com", "A", 300, &["10.0.0.1"])];
+
+    for (mode, rolled_back) in [("atomic", true), ("best-effort", false)] {
+        let pdns = MockPdns::start().await;
+        pdns.add_zone("example.com", original.clone());
+        pdns.add_zone("example.net", vec![]);
+        pdns.reject_patches("example.net");
+
+        let cfg = pdns.config(&[("APPLY_MODE", mode)]);
+        let (status, body) = send(test_router(cfg), "POST", "/records", Some(changes.clone())).await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY, "{mode}: {body}");
+
+        let rrsets = pdns.rrsets("example.com");
+        if rolled_back {
+            assert!(body["error"].as_str().unwrap().contains("rolled back 1 zone(s)"), "{body}");
+            assert_eq!(rrsets.len(), 1);
+            assert_eq!(rrsets[0]["records"], original[0]["records"]);
+        } else {
+            assert_eq!(rrsets.len(), 2, "{mode}");
+        }
+    }
+}
+
+/// An atomic rollback restores the comments an RRset had, not just its
+/// records: the meta comment the failed batch wrote is gone again.
+#[tokio::test]
+async fn test_atomic_rollback_restores_comments() {
+    let pdns = MockPdns::start().await;
+    let mut original = rrset("www.example.com", "A", 300, &["10.0.0.1"]);
+    original["comments"] = json!([{ "content": "pinned by ops", "account": "ops" }]);
+    pdns.add_zone("example.com", vec![original.clone()]);
+    pdns.add_zone("example.net", vec![]);
+    pdns.reject_patches("example.net");
+
+    let mut new = endpoint("www.example.com", "A", &["10.0.0.2"]);
+    new["labels"] = json!({ "owner": "cluster-a" });
+    let changes = json!({
+        "create":    [endpoint("www.example.net", "A", &["10.0.0.4"])],
+        "updateOld": [endpoint("www.example.com", "A", &["10.0.0.1"])],
+        "updateNew": [new],
+    });
+    let cfg = pdns.config(&[("APPLY_MODE", "atomic")]);
+    let (status, body) = send(test_router(cfg), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY, "{body}");
+
+    let rrsets = pdns.rrsets("example.com");
+    assert_eq!(rrsets[0]["records"], original["records"]);
+    assert_eq!(rrsets[0]["comments"], original["comments"]);
+}
+
+/// An update keeps comments other accounts left on the RRset, and they
+/// don't make an otherwise identical RRset look changed.
+#[tokio::test]
+async fn test_update_keeps_foreign_comments() {
+    let pdns = MockPdns::start().await;
+    let mut original = rrset("www.example.com", "A", 300, &["10.0.0.1"]);
+    original["comments"] = json!([{ "content": "pinned by ops", "account": "ops" }]);
+    pdns.add_zone("example.com", vec![original.clone()]);
+
+    let changes = json!({
+        "updateOld": [endpoint("www.example.com", "A", &["10.0.0.1"])],
+        "updateNew": [endpoint("www.example.com", "A", &["10.0.0.2"])],
+    });
+    let cfg = pdns.config(&[]);
+    let (status, body) = send(test_router(cfg.clone()), "POST", "/records", Some(changes.clone())).await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{body}");
+
+    let rrsets = pdns.rrsets("example.com");
+    assert_eq!(rrsets[0]["records"][0]["content"], "10.0.0.2");
+    assert_eq!(rrsets[0]["comments"], original["comments"]);
+
+    // The same sync again finds nothing to do.
+    send(test_router(cfg), "POST", "/records", Some(changes)).await;
+    assert_eq!(pdns.patches().len(), 1);
+}
+
+/// With APPLY_MODE=continue every endpoint is attempted and all failures
+/// are reported together.
+#[tokio::test]
+async fn test_continue_apply_reports_every_failure() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    pdns.add_zone("example.net", vec![]);
+    pdns.reject_patches("example.net");
+
+    let changes = json!({
+        "create": [
+            endpoint("a.example.com", "A", &["10.0.0.1"]),
+            endpoint("b.example.net", "A", &["10.0.0.2"]),
+            endpoint("c.example.org", "A", &["10.0.0.3"]),
+        ],
+    });
+    let cfg = pdns.config(&[("APPLY_MODE", "continue")]);
+    let (status, body) = send(test_router(cfg), "POST", "/records", Some(changes)).await;
+    assert!(!status.is_success(), "{body}");
+
+    let mut failed: Vec<&str> = body["failed"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| f["dnsName"].as_str().unwrap())
+        .collect();
+    failed.sort();
+    assert_eq!(failed, ["b.example.net", "c.example.org"]);
+    assert_eq!(body["applied"], 1);
+    assert_eq!(pdns.rrsets("example.com").len(), 1);
+}
+
+/// With AUTO_CREATE_ZONES a missing zone under DOMAIN_FILTER is created
+/// once and written to; preview only names it.
+#[tokio::test]
+async fn test_auto_create_missing_zone() {
+    let pdns = MockPdns::start().await;
+    let create = "POST /api/v1/servers/localhost/zones";
+    let cfg = pdns.config(&[
+        ("AUTO_CREATE_ZONES", "true"),
+        ("AUTO_CREATE_ZONE_KIND", "master"),
+        ("DOMAIN_FILTER", "example.com"),
+    ]);
+    let changes = json!({
+        "create": [
+            endpoint("www.example.com", "A", &["10.0.0.1"]),
+            endpoint("api.example.com", "A", &["10.0.0.2"]),
+        ],
+    });
+
+    let (status, body) =
+        send(test_router(cfg.clone()), "POST", "/records/preview", Some(changes.clone())).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["zones"][0]["zone"], "example.com.");
+    assert_eq!(pdns.count(create), 0);
+
+    let (status, body) = send(test_router(cfg), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{body}");
+    assert_eq!(pdns.count(create), 1);
+    assert_eq!(pdns.rrsets("example.com").len(), 2);
+
+    // Off by default: the missing zone is still an error.
+    let pdns = MockPdns::start().await;
+    let changes = json!({ "create": [endpoint("www.example.com", "A", &["10.0.0.1"])] });
+    let cfg = pdns.config(&[("DOMAIN_FILTER", "example.com")]);
+    let (status, _) = send(test_router(cfg), "POST", "/records", Some(changes)).await;
+    assert!(!status.is_success());
+    assert_eq!(pdns.count(create), 0);
+}
+
+/// DRY_RUN answers POST /records as if it had applied the changes, but
+/// sends PowerDNS no PATCH, zone creation, NOTIFY or rectify.
+#[tokio::test]
+async fn test_dry_run_sends_no_writes() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![rrset("old.example.com", "A", 300, &["10.0.0.9"])]);
+    let cfg = pdns.config(&[
+        ("DRY_RUN", "true"),
+        ("AUTO_CREATE_ZONES", "true"),
+        ("DOMAIN_FILTER", "example.com,example.org"),
+        ("NOTIFY_AFTER_APPLY", "true"),
+    ]);
+    let changes = json!({
+        "create": [
+            endpoint("www.example.com", "A", &["10.0.0.1"]),
+            endpoint("www.example.org", "A", &["10.0.0.2"]),
+        ],
+        "delete": [endpoint("old.example.com", "A", &["10.0.0.9"])],
+    });
+
+    let (status, body) = send(test_router(cfg.clone()), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{body}");
+    assert!(pdns.requests().iter().all(|r| r.starts_with("GET ")), "{:?}", pdns.requests());
+    assert_eq!(pdns.rrsets("example.com").len(), 1);
+
+    let (status, listed) = send(test_router(cfg), "GET", "/records", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(listed[0]["dnsName"], "old.example.com");
+}
+
+/// With PRESERVE_EXISTING_TTL a TTL set by hand in PowerDNS survives a sync
+/// that carries no TTL; new records still get DEFAULT_TTL.
+#[tokio::test]
+async fn test_preserve_existing_ttl() {
+    let changes = json!({
+        "updateOld": [endpoint("www.example.com", "A", &["10.0.0.1"])],
+        "updateNew": [endpoint("www.example.com", "A", &["10.0.0.1"])],
+        "create":    [endpoint("new.example.com", "A", &["10.0.0.2"])],
+    });
+
+    for (preserve, www_ttl) in [("true", 3600), ("false", 300)] {
+        let pdns = MockPdns::start().await;
+        pdns.add_zone("example.com", vec![rrset("www.example.com", "A", 3600, &["10.0.0.1"])]);
+
+        let cfg = pdns.config(&[("PRESERVE_EXISTING_TTL", preserve)]);
+        let (status, body) = send(test_router(cfg), "POST", "/records", Some(changes.clone())).await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{body}");
+
+        let rrsets = pdns.rrsets("example.com");
+        let ttl = |name: &str| rrsets.iter().find(|r| r["name"] == name).unwrap()["ttl"].clone();
+        assert_eq!(ttl("www.example.com."), www_ttl, "PRESERVE_EXISTING_TTL={preserve}");
+        assert_eq!(ttl("new.example.com."), 300, "PRESERVE_EXISTING_TTL={preserve}");
+    }
+}
+
+/// NOTIFY_AFTER_APPLY / RECTIFY_AFTER_APPLY act once per patched zone, and
+/// their failure doesn't fail the apply.
+#[tokio::test]
+async fn test_notify_and_rectify_once_per_zone() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    pdns.add_zone("example.net", vec![]);
+    let changes = json!({
+        "create": [
+            endpoint("a.example.com", "A", &["10.0.0.1"]),
+            endpoint("b.example.com", "A", &["10.0.0.2"]),
+            endpoint("c.example.net", "A", &["10.0.0.3"]),
+        ],
+    });
+    let cfg = pdns.config(&[("NOTIFY_AFTER_APPLY", "true"), ("RECTIFY_AFTER_APPLY", "true")]);
+
+    let (status, _) = send(test_router(cfg.clone()), "POST", "/records", Some(changes.clone())).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    for zone in ["example.com.", "example.net."] {
+        for action in ["notify", "rectify"] {
+            let request = format!("PUT /api/v1/servers/localhost/zones/{zone}/{action}");
+            assert_eq!(pdns.count(&request), 1, "{request}");
+        }
+    }
+
+    pdns.state.lock().unwrap().reject_zone_actions = true;
+    let (status, _) = send(test_router(cfg), "POST", "/records", Some(changes.clone())).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    // Off by default.
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    pdns.add_zone("example.net", vec![]);
+    send(test_router(pdns.config(&[])), "POST", "/records", Some(changes)).await;
+    assert!(pdns.requests().iter().all(|r| !r.starts_with("PUT")));
+}
+
+/// An upsert identical to what PowerDNS holds is skipped, along with the
+/// DELETE of its update pair; trailing dots and record order don't count as
+/// differences.
+#[tokio::test]
+async fn test_unchanged_upserts_are_skipped() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone(
+        "example.com",
+        vec![
+            rrset("www.example.com", "A", 300, &["10.0.0.2", "10.0.0.1"]),
+            rrset("alias.example.com", "CNAME", 300, &["lb.example.com."]),
+        ],
+    );
+    let cfg = pdns.config(&[]);
+
+    let changes = json!({
+        "updateOld": [endpoint("www.example.com", "A", &["10.0.0.1", "10.0.0.2"])],
+        "updateNew": [endpoint("www.example.com", "A", &["10.0.0.1", "10.0.0.2"])],
+        "create":    [endpoint("alias.example.com", "CNAME", &["lb.example.com"])],
+    });
+    let (status, _) = send(test_router(cfg.clone()), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(pdns.patches().is_empty(), "{:?}", pdns.patches());
+    assert_eq!(pdns.rrsets("example.com").len(), 2);
+
+    // A TTL change is a real change.
+    let mut changed = endpoint("www.example.com", "A", &["10.0.0.1", "10.0.0.2"]);
+    changed["recordTTL"] = json!(600);
+    let changes = json!({ "updateOld": [changed.clone()], "updateNew": [changed] });
+    let (status, _) = send(test_router(cfg), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(pdns.patches().len(), 1);
+}
+
+/// Deleting a record that is already gone succeeds without a PATCH.
+#[tokio::test]
+async fn test_delete_of_absent_record_succeeds() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![rrset("www.example.com", "A", 300, &["10.0.0.1"])]);
+
+    let changes = json!({
+        "delete": [
+            endpoint("gone.example.com", "A", &["10.0.0.9"]),
+            endpoint("www.example.com", "AAAA", &["2001:db8::1"]),
+        ],
+    });
+    let (status, body) = send(test_router(pdns.config(&[])), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{body}");
+    assert!(pdns.patches().is_empty());
+    assert_eq!(pdns.rrsets("example.com").len(), 1);
+}
+
+/// Written TTLs are clamped to MIN_TTL/MAX_TTL; an unset TTL takes the
+/// default before clamping.
+#[tokio::test]
+async fn test_preview_clamps_ttls() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+
+    let with_ttl = |name: &str, ttl: u32| {
+        let mut ep = endpoint(name, "A", &["10.0.0.1"]);
+        ep["recordTTL"] = json!(ttl);
+        ep
+    };
+    let changes = json!({
+        "create": [
+            with_ttl("low.example.com", 1),
+            with_ttl("high.example.com", 86400),
+            with_ttl("unset.example.com", 0),
+            with_ttl("edge.example.com", 60),
+        ],
+    });
+
+    let cfg = pdns.config(&[("DEFAULT_TTL", "30"), ("MIN_TTL", "60"), ("MAX_TTL", "3600")]);
+    let (status, body) = send(test_router(cfg), "POST", "/records/preview", Some(changes)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    let ttls: Vec<u64> = body["zones"][0]["rrsets"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["ttl"].as_u64().unwrap())
+        .collect();
+    assert_eq!(ttls, [60, 3600, 60, 60]);
+}
+
+/// Zones fetched in parallel still contribute every endpoint.
+#[tokio::test]
+async fn test_get_records_collects_all_zones_in_parallel() {
+    let pdns = MockPdns::start().await;
+    for i in 0..20 {
+        let zone = format!("zone{i}.example");
+        pdns.add_zone(&zone, vec![rrset(&format!("www.{zone}"), "A", 300, &["10.0.0.1"])]);
+    }
+
+    let app = test_router(pdns.config(&[("PDNS_FETCH_CONCURRENCY", "3")]));
+    let (status, body) = send(app, "GET", "/records", None).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    let mut names: Vec<&str> = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|ep| ep["dnsName"].as_str().unwrap())
+        .collect();
+    names.sort();
+    let mut expected: Vec<String> = (0..20).map(|i| format!("www.zone{i}.example")).collect();
+    expected.sort();
+    assert_eq!(names, expected);
+}
+
+/// With both a parent and a child zone hosted, names go to the longest
+/// matching zone, and only on a label boundary.  PowerDNS lists `eu.` before
+/// `example.com.` and `sub.` after it, so both listing orders are covered.
+#[tokio::test]
+async fn test_zone_resolution_prefers_longest_match() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    pdns.add_zone("sub.example.com", vec![]);
+    pdns.add_zone("eu.example.com", vec![]);
+
+    let changes = json!({
+        "create": [
+            endpoint("www.sub.example.com",  "A", &["10.0.0.1"]),
+            endpoint("www.xsub.example.com", "A", &["10.0.0.2"]),
+            endpoint("db.eu.example.com",    "A", &["10.0.0.3"]),
+        ],
+    });
+
+    let (status, body) = send(test_router(pdns.config(&[])), "POST", "/records/preview", Some(changes)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["zones"][0]["zone"], "sub.example.com.");
+    assert_eq!(body["zones"][0]["rrsets"][0]["name"], "www.sub.example.com.");
+    assert_eq!(body["zones"][1]["zone"], "example.com.");
+    assert_eq!(body["zones"][1]["rrsets"][0]["name"], "www.xsub.example.com.");
+    assert_eq!(body["zones"][2]["zone"], "eu.example.com.");
+    assert_eq!(body["zones"][2]["rrsets"][0]["name"], "db.eu.example.com.");
+}
+
+/// Records at a zone's own name go into that zone, not its parent.
+#[tokio::test]
+async fn test_apex_records_use_their_own_zone() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    pdns.add_zone("sub.example.com", vec![]);
+
+    let changes = json!({
+        "create": [
+            endpoint("sub.example.com", "A",   &["10.0.0.1"]),
+            endpoint("example.com",     "TXT", &["v=spf1 -all"]),
+        ],
+    });
+    let (status, body) = send(test_router(pdns.config(&[])), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{body}");
+
+    let sub = pdns.rrsets("sub.example.com");
+    assert_eq!(sub.len(), 1);
+    assert_eq!((&sub[0]["name"], &sub[0]["type"]), (&json!("sub.example.com."), &json!("A")));
+    let parent = pdns.rrsets("example.com");
+    assert_eq!(parent.len(), 1);
+    assert_eq!((&parent[0]["name"], &parent[0]["type"]), (&json!("example.com."), &json!("TXT")));
+    assert_eq!(parent[0]["records"][0]["content"], "\"v=spf1 -all\"");
+}
+
+/// All changes to a zone go out in one PATCH, deletes first, and the
+/// update-old DELETE is folded into the update-new REPLACE.
+#[tokio::test]
+async fn test_apply_sends_one_patch_per_zone() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![
+        rrset("old.example.com", "A", 300, &["10.0.0.9"]),
+        rrset("www.example.com", "A", 300, &["10.0.0.1"]),
+    ]);
+    pdns.add_zone("example.net", vec![]);
+
+    let changes = json!({
+        "create":    [
+            endpoint("new.example.com", "A", &["10.0.0.2"]),
+            endpoint("api.example.net", "A", &["10.0.0.3"]),
+        ],
+        "updateOld": [endpoint("www.example.com", "A", &["10.0.0.1"])],
+        "updateNew": [endpoint("www.example.com", "A", &["10.0.0.5"])],
+        "delete":    [endpoint("old.example.com", "A", &["10.0.0.9"])],
+    });
+
+    let (status, body) = send(test_router(pdns.config(&[])), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{body}");
+
+    let patches = pdns.patches();
+    assert_eq!(patches.len(), 2);
+    assert_eq!(pdns.count("PATCH /api/v1/servers/localhost/zones/example.com."), 1);
+    assert_eq!(pdns.count("PATCH /api/v1/servers/localhost/zones/example.net."), 1);
+
+    let ops: Vec<(&str, &str)> = patches[0]["rrsets"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| (r["changetype"].as_str().unwrap(), r["name"].as_str().unwrap()))
+        .collect();
+    assert_eq!(ops, [
+        ("DELETE",  "old.example.com."),
+        ("REPLACE", "www.example.com."),
+        ("REPLACE", "new.example.com."),
+    ]);
+    assert_eq!(pdns.rrsets("example.com").len(), 2);
+}
+
+/// Same-name endpoints with different TTLs merge deterministically per
+/// MERGED_TTL_STRATEGY.
+#[tokio::test]
+async fn test_merged_ttl_strategy() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+
+    let changes = json!({
+        "create": [
+            { "dnsName": "www.example.com", "recordType": "A", "recordTTL": 600, "targets": ["10.0.0.1"] },
+            { "dnsName": "www.example.com", "recordType": "A", "recordTTL": 60,  "targets": ["10.0.0.2"] },
+        ],
+    });
+
+    for (strategy, expected) in [("first", 600), ("min", 60), ("max", 600)] {
+        let cfg = pdns.config(&[("MERGED_TTL_STRATEGY", strategy)]);
+        let (status, body) =
+            send(test_router(cfg), "POST", "/records/preview", Some(changes.clone())).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let rrsets = body["zones"][0]["rrsets"].as_array().unwrap();
+        assert_eq!(rrsets.len(), 1);
+        assert_eq!(rrsets[0]["ttl"], expected, "strategy {strategy}");
+    }
+}
+
+/// The preview lists at most PREVIEW_MAX_CHANGES changes and summarises the
+/// rest.
+#[tokio::test]
+async fn test_preview_truncates_at_max_changes() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    pdns.add_zone("example.net", vec![]);
+
+    let two = json!({
+        "create": [
+            endpoint("a.example.com", "A", &["10.0.0.1"]),
+            endpoint("b.example.net", "A", &["10.0.0.2"]),
+        ],
+    });
+    let three = json!({
+        "create": [
+            endpoint("a.example.com", "A", &["10.0.0.1"]),
+            endpoint("b.example.net", "A", &["10.0.0.2"]),
+            endpoint("c.example.net", "A", &["10.0.0.3"]),
+        ],
+    });
+    let cfg = pdns.config(&[("PREVIEW_MAX_CHANGES", "2")]);
+
+    // Exactly at the limit: nothing omitted.
+    let (_, body) = send(test_router(cfg.clone()), "POST", "/records/preview", Some(two)).await;
+    assert_eq!(body["totalChanges"], 2);
+    assert!(body.get("omitted").is_none());
+    assert_eq!(body["zones"].as_array().unwrap().len(), 2);
+
+    // One over: the last change is cut.
+    let (_, body) = send(test_router(cfg), "POST", "/records/preview", Some(three)).await;
+    assert_eq!(body["totalChanges"], 3);
+    assert_eq!(body["omitted"], "...1 more changes omitted");
+    assert_eq!(body["zones"][1]["rrsets"].as_array().unwrap().len(), 1);
+    assert_eq!(body["zones"][1]["rrsets"][0]["name"], "b.example.net.");
+}
+
+/// A and AAAA targets must be IPv4 and IPv6 addresses respectively; a bad
+/// one fails the request with 422 before anything reaches PowerDNS.
+#[tokio::test]
+async fn test_address_target_validation() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+
+    let valid = [("A", "10.0.0.1"), ("A", " 192.0.2.7 "), ("AAAA", "2001:db8::1"), ("AAAA", "::ffff:10.0.0.1")];
+    for (rtype, valid) in valid {
+        let changes = json!({ "create": [endpoint("www.example.com", rtype, &[valid])] });
+        let (status, body) =
+            send(test_router(pdns.config(&[])), "POST", "/records/preview", Some(changes)).await;
+        assert_eq!(status, StatusCode::OK, "{rtype} {valid}: {body}");
+    }
+
+    let invalid = [
+        ("A", "example.com"),
+        ("A", "10.0.0.256"),
+        ("A", "2001:db8::1"),
+        ("AAAA", "10.0.0.1"),
+        ("AAAA", "2001:db8::g"),
+    ];
+    for (rtype, invalid) in invalid {
+        let changes = json!({ "create": [endpoint("www.example.com", rtype, &[invalid])] });
+        let (status, body) =
+            send(test_router(pdns.config(&[])), "POST", "/records", Some(changes)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{rtype} {invalid}: {body}");
+        let error = body["error"].as_str().unwrap();
+        assert!(error.contains("www.example.com") && error.contains(invalid), "{error}");
+    }
+    assert!(pdns.patches().is_empty());
+}
+
+/// A CNAME can only have one target; more fails the request with 422.
+#[tokio::test]
+async fn test_cname_with_multiple_targets_rejected() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+
+    let changes = json!({ "create": [endpoint("www.example.com", "CNAME", &["a.example.com", "b.example.com"])] });
+    let (status, body) = send(test_router(pdns.config(&[])), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{body}");
+    let error = body["error"].as_str().unwrap();
+    assert!(error.contains("www.example.com") && error.contains("only one"), "{error}");
+    assert!(pdns.patches().is_empty());
+}
+
+/// A CNAME and another type written at the same name in one batch are
+/// rejected with 422; CNAMEs at distinct names are fine.
+#[tokio::test]
+async fn test_cname_coexisting_with_other_type_rejected() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+
+    let changes = json!({
+        "create":    [endpoint("www.example.com", "A", &["10.0.0.1"]),
+                      endpoint("api.example.com", "CNAME", &["lb.example.com"])],
+        "updateNew": [endpoint("WWW.example.com.", "CNAME", &["lb.example.com"])],
+    });
+    let (status, body) = send(test_router(pdns.config(&[])), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{body}");
+    let error = body["error"].as_str().unwrap();
+    assert!(error.contains("www.example.com: CNAME alongside A"), "{error}");
+    assert!(!error.contains("api.example.com"), "{error}");
+    assert!(pdns.patches().is_empty());
+
+    let changes = json!({
+        "create": [endpoint("www.example.com", "A", &["10.0.0.1"]),
+                   endpoint("api.example.com", "CNAME", &["lb.example.com"])],
+    });
+    let (status, body) = send(test_router(pdns.config(&[])), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{body}");
+}
+
+/// An expanded IPv6 address is written in PowerDNS's compressed form, so
+/// re-applying the expanded form reads back identically.
+#[tokio::test]
+async fn test_aaaa_targets_written_compressed() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    let cfg = pdns.config(&[]);
+
+    let expanded = "2001:0db8:0000:0000:0000:0000:0000:0001";
+    let changes = json!({ "create": [endpoint("www.example.com", "AAAA", &[expanded])] });
+    let (status, _) = send(test_router(cfg.clone()), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(pdns.rrsets("example.com")[0]["records"][0]["content"], "2001:db8::1");
+
+    let (_, body) = send(test_router(cfg), "GET", "/records", None).await;
+    assert_eq!(body[0]["targets"], json!(["2001:db8::1"]));
+}
+
+/// HTTPS targets without a SvcPriority get "1", and bare SvcParams get the
+/// "." TargetName rather than being mistaken for a hostname.
+#[tokio::test]
+async fn test_https_target_normalisation() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+
+    let cases = [
+        ("alpn=h2,h3", "1 . alpn=h2,h3"),
+        ("alpn=h2 port=8443", "1 . alpn=h2 port=8443"),
+        ("svc.example.com", "1 svc.example.com."),
+        ("svc.example.com alpn=h2", "1 svc.example.com. alpn=h2"),
+        ("1 . alpn=h2", "1 . alpn=h2"),
+        ("2 svc.example.com alpn=h3", "2 svc.example.com. alpn=h3"),
+    ];
+    for (target, expected) in cases {
+        let changes = json!({ "create": [endpoint("www.example.com", "HTTPS", &[target])] });
+        let (status, body) =
+            send(test_router(pdns.config(&[])), "POST", "/records/preview", Some(changes)).await;
+        assert_eq!(status, StatusCode::OK, "{target}: {body}");
+        assert_eq!(body["zones"][0]["rrsets"][0]["records"][0]["content"], expected, "{target}");
+    }
+}
+
+/// HTTPS targets must end up with a TargetName of "." or a valid hostname,
+/// and SvcParams with known keys and well-formed values.
+#[tokio::test]
+async fn test_https_target_validation() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+
+    let valid = [
+        "1 . alpn=h2",
+        "1 svc.example.com alpn=h2,h3",
+        "2 _svc.example.com.",
+        "1 . alpn=h2 no-default-alpn port=8443 ipv4hint=192.0.2.1,192.0.2.2 mandatory=alpn,port",
+        "1 . ipv6hint=2001:db8::1 ech=AEX+DQBB",
+    ];
+    for valid in valid {
+        let changes = json!({ "create": [endpoint("www.example.com", "HTTPS", &[valid])] });
+        let (status, body) =
+            send(test_router(pdns.config(&[])), "POST", "/records/preview", Some(changes)).await;
+        assert_eq!(status, StatusCode::OK, "{valid}: {body}");
+    }
+
+    let invalid = [
+        "1 bad..example.com alpn=h2",
+        "1 -bad.example.com",
+        "1 bad!.example.com",
+        "1 . alnp=h2",
+        "1 . alpn",
+        "1 . port=http",
+        "1 . ipv4hint=192.0.2.1,2001:db8::1",
+        "1 . ipv6hint=192.0.2.1",
+        "1 . mandatory=alpn,bogus",
+    ];
+    for invalid in invalid {
+        let changes = json!({ "create": [endpoint("www.example.com", "HTTPS", &[invalid])] });
+        let (status, body) =
+            send(test_router(pdns.config(&[])), "POST", "/records", Some(changes)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{invalid}: {body}");
+        assert!(body["error"].as_str().unwrap().contains("www.example.com"));
+    }
+    assert!(pdns.patches().is_empty());
+}
+
+/// An MX record reads back from PowerDNS and re-applies unchanged: only the
+/// exchange hostname is dotted, never the preference.
+#[tokio::test]
+async fn test_mx_round_trip_is_stable() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+
+    let changes = json!({ "create": [endpoint("sub.example.com", "MX", &["10 mail.example.com"])] });
+    let cfg = pdns.config(&[]);
+    let (status, _) = send(test_router(cfg.clone()), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(pdns.rrsets("example.com")[0]["records"][0]["content"], "10 mail.example.com.");
+
+    let (_, first) = send(test_router(cfg.clone()), "GET", "/records", None).await;
+    let mx = first.as_array().unwrap().iter().find(|ep| ep["recordType"] == "MX").unwrap();
+
+    let changes = json!({ "updateOld": [mx], "updateNew": [mx] });
+    let (status, _) = send(test_router(cfg.clone()), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (_, second) = send(test_router(cfg), "GET", "/records", None).await;
+    assert_eq!(first, second);
+}
+
+/// A CAA record survives read → apply → read unchanged, quotes intact and
+/// with no trailing dot added.
+#[tokio::test]
+async fn test_caa_round_trip_is_stable() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+
+    let changes = json!({ "create": [endpoint("www.example.com", "CAA", &["0 issue \"letsencrypt.org\""])] });
+    let cfg = pdns.config(&[]);
+    let (status, _) = send(test_router(cfg.clone()), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(pdns.rrsets("example.com")[0]["records"][0]["content"], "0 issue \"letsencrypt.org\"");
+
+    let (_, first) = send(test_router(cfg.clone()), "GET", "/records", None).await;
+    let caa = first.as_array().unwrap().iter().find(|ep| ep["recordType"] == "CAA").unwrap();
+    assert_eq!(caa["targets"], json!(["0 issue \"letsencrypt.org\""]));
+
+    let changes = json!({ "updateOld": [caa], "updateNew": [caa] });
+    let (status, _) = send(test_router(cfg.clone()), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (_, second) = send(test_router(cfg), "GET", "/records", None).await;
+    assert_eq!(first, second);
+}
+
+/// An ALIAS at the zone apex is written with a trailing dot and read back
+/// without one, so it round-trips unchanged.
+#[tokio::test]
+async fn test_apex_alias_round_trip_is_stable() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+
+    let changes = json!({ "create": [endpoint("example.com", "ALIAS", &["lb.example.net"])] });
+    let cfg = pdns.config(&[]);
+    let (status, _) = send(test_router(cfg.clone()), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(pdns.rrsets("example.com")[0]["records"][0]["content"], "lb.example.net.");
+
+    let (_, first) = send(test_router(cfg.clone()), "GET", "/records", None).await;
+    let alias = first.as_array().unwrap().iter().find(|ep| ep["recordType"] == "ALIAS").unwrap();
+    assert_eq!(alias["dnsName"], "example.com");
+    assert_eq!(alias["targets"], json!(["lb.example.net"]));
+
+    let changes = json!({ "updateOld": [alias], "updateNew": [alias] });
+    let (status, _) = send(test_router(cfg.clone()), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (_, second) = send(test_router(cfg), "GET", "/records", None).await;
+    assert_eq!(first, second);
+}
+
+/// A lowercase record type is handled as its uppercase form: a `cname` is
+/// written as CNAME with a dotted target and reported back as CNAME.
+#[tokio::test]
+async fn test_lowercase_record_type_is_normalised() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+
+    let changes = json!({ "create": [endpoint("www.example.com", "cname", &["lb.example.net"])] });
+    let cfg = pdns.config(&[]);
+    let (status, _) = send(test_router(cfg.clone()), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let stored = &pdns.rrsets("example.com")[0];
+    assert_eq!(stored["type"], "CNAME");
+    assert_eq!(stored["records"][0]["content"], "lb.example.net.");
+
+    let (_, listed) = send(test_router(cfg), "GET", "/records", None).await;
+    assert_eq!(listed[0]["recordType"], "CNAME");
+    assert_eq!(listed[0]["targets"], json!(["lb.example.net"]));
+}
+
+/// Names are matched case-insensitively and written and reported lowercase:
+/// `WWW.Example.COM` is the same record as `www.example.com`.
+#[tokio::test]
+async fn test_mixed_case_names_are_canonicalised() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![rrset("www.example.com", "A", 300, &["10.0.0.1"])]);
+    pdns.add_zone("Example.ORG", vec![rrset("API.Example.ORG", "TXT", 300, &["\"Mixed Case\""])]);
+    let cfg = pdns.config(&[("DOMAIN_FILTER", "example.com,EXAMPLE.org")]);
+
+    let same = json!({ "create": [endpoint("WWW.Example.COM", "A", &["10.0.0.1"])] });
+    let (status, _) = send(test_router(cfg.clone()), "POST", "/records", Some(same)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(pdns.patches().is_empty());
+
+    let changed = json!({ "create": [endpoint("WWW.Example.COM.", "A", &["10.0.0.2"])] });
+    let (status, _) = send(test_router(cfg.clone()), "POST", "/records", Some(changed)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let stored = pdns.rrsets("example.com");
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0]["name"], "www.example.com.");
+
+    let (_, listed) = send(test_router(cfg), "GET", "/records", None).await;
+    let txt = listed.as_array().unwrap().iter().find(|ep| ep["recordType"] == "TXT").unwrap();
+    assert_eq!(txt["dnsName"], "api.example.org");
+    assert_eq!(txt["targets"], json!(["Mixed Case"]));
+}
+
+/// Unicode names are written to PowerDNS as punycode and reported back as
+/// the Unicode name that was sent; targets are left alone.
+#[tokio::test]
+async fn test_idn_names_round_trip_as_unicode() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    pdns.add_zone("xn--mnchen-3ya.de", vec![]);
+    let cfg = pdns.config(&[("DOMAIN_FILTER", "example.com,münchen.de")]);
+
+    let changes = json!({
+        "create": [
+            endpoint("café.example.com", "A", &["10.0.0.1"]),
+            endpoint("www.München.de", "CNAME", &["lb.example.net"]),
+        ],
+    });
+    let (status, body) = send(test_router(cfg.clone()), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{body}");
+    assert_eq!(pdns.rrsets("example.com")[0]["name"], "xn--caf-dma.example.com.");
+    let cname = &pdns.rrsets("xn--mnchen-3ya.de")[0];
+    assert_eq!(cname["name"], "www.xn--mnchen-3ya.de.");
+    assert_eq!(cname["records"][0]["content"], "lb.example.net.");
+
+    let (_, listed) = send(test_router(cfg), "GET", "/records", None).await;
+    let names: Vec<&str> =
+        listed.as_array().unwrap().iter().map(|ep| ep["dnsName"].as_str().unwrap()).collect();
+    assert_eq!(names, ["café.example.com", "www.münchen.de"]);
+}
+
+/// With several PDNS_SERVER_IDs each name is written to the server hosting
+/// its zone, and GET /records lists the zones of all of them.
+#[tokio::test]
+async fn test_multiple_servers_route_by_zone() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![rrset("www.example.com", "A", 300, &["10.0.0.1"])]);
+    pdns.add_zone_on("internal", "corp.example", vec![rrset("db.corp.example", "A", 300, &["10.1.0.1"])]);
+    let cfg = pdns.config(&[("PDNS_SERVER_ID", "localhost, internal")]);
+
+    let (_, listed) = send(test_router(cfg.clone()), "GET", "/records", None).await;
+    let names: Vec<&str> =
+        listed.as_array().unwrap().iter().map(|ep| ep["dnsName"].as_str().unwrap()).collect();
+    assert_eq!(names, ["db.corp.example", "www.example.com"]);
+
+    let changes = json!({
+        "create": [
+            endpoint("api.example.com", "A", &["10.0.0.2"]),
+            endpoint("cache.corp.example", "A", &["10.1.0.2"]),
+        ],
+    });
+    let (status, body) = send(test_router(cfg), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{body}");
+    assert_eq!(pdns.count("PATCH /api/v1/servers/localhost/zones/example.com."), 1);
+    assert_eq!(pdns.count("PATCH /api/v1/servers/internal/zones/corp.example."), 1);
+    assert_eq!(pdns.rrsets("corp.example").len(), 2);
+}
+
+/// Slave and Consumer zones are left out of GET /records and refuse writes,
+/// even where a primary parent zone exists, unless MANAGE_SLAVE_ZONES is set.
+#[tokio::test]
+async fn test_secondary_zones_are_not_managed() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![rrset("www.example.com", "A", 300, &["10.0.0.1"])]);
+    pdns.add_zone("replica.example.com", vec![rrset("db.replica.example.com", "A", 300, &["10.0.1.1"])]);
+    pdns.add_zone("catalog.example.org", vec![rrset("x.catalog.example.org", "TXT", 300, &["\"m\""])]);
+    pdns.set_kind("example.com", "Master");
+    pdns.set_kind("replica.example.com", "Slave");
+    pdns.set_kind("catalog.example.org", "Consumer");
+
+    let cfg = pdns.config(&[]);
+    let (_, listed) = send(test_router(cfg.clone()), "GET", "/records", None).await;
+    let names: Vec<&str> =
+        listed.as_array().unwrap().iter().map(|ep| ep["dnsName"].as_str().unwrap()).collect();
+    assert_eq!(names, ["www.example.com"]);
+
+    let changes = json!({ "create": [endpoint("api.replica.example.com", "A", &["10.0.1.2"])] });
+    let (status, body) = send(test_router(cfg), "POST", "/records", Some(changes.clone())).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(body["error"].as_str().unwrap().contains("MANAGE_SLAVE_ZONES"), "{body}");
+    assert!(pdns.patches().is_empty());
+
+    let cfg = pdns.config(&[("MANAGE_SLAVE_ZONES", "true")]);
+    let (_, listed) = send(test_router(cfg.clone()), "GET", "/records", None).await;
+    assert_eq!(listed.as_array().unwrap().len(), 3);
+    let (status, _) = send(test_router(cfg), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(pdns.rrsets("replica.example.com").len(), 2);
+}
+
+/// Records an operator disabled stay hidden from GET /records and survive
+/// writes: an update neither enables nor drops them, and a delete leaves
+/// them in place.
+#[tokio::test]
+async fn test_disabled_records_are_preserved() {
+    let pdns = MockPdns::start().await;
+    let mut www = rrset("www.example.com", "A", 300, &["10.0.0.1", "10.0.0.2"]);
+    www["records"][1]["disabled"] = json!(true);
+    pdns.add_zone("example.com", vec![www]);
+    let cfg = pdns.config(&[]);
+    let records = |pdns: &MockPdns| -> Vec<(String, bool)> {
+        pdns.rrsets("example.com")[0]["records"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| (r["content"].as_str().unwrap().to_string(), r["disabled"].as_bool().unwrap()))
+            .collect()
+    };
+
+    let (_, listed) = send(test_router(cfg.clone()), "GET", "/records", None).await;
+    assert_eq!(listed[0]["targets"], json!(["10.0.0.1"]));
+
+    let changes = json!({
+        "updateOld": [endpoint("www.example.com", "A", &["10.0.0.1"])],
+        "updateNew": [endpoint("www.example.com", "A", &["10.0.0.1", "10.0.0.2", "10.0.0.3"])],
+    });
+    let (status, body) = send(test_router(cfg.clone()), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{body}");
+    assert_eq!(records(&pdns), [
+        ("10.0.0.1".to_string(), false),
+        ("10.0.0.2".to_string(), true),
+        ("10.0.0.3".to_string(), false),
+    ]);
+
+    let delete = json!({ "delete": [endpoint("www.example.com", "A", &["10.0.0.1", "10.0.0.3"])] });
+    let (status, _) = send(test_router(cfg.clone()), "POST", "/records", Some(delete.clone())).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(records(&pdns), [("10.0.0.2".to_string(), true)]);
+
+    let patches = pdns.patches().len();
+    let (status, _) = send(test_router(cfg), "POST", "/records", Some(delete)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(pdns.patches().len(), patches);
+}
+
+/// A PTR in an IPv4 reverse zone lands in that zone with a dotted target
+/// and is listed back without the dot.
+#[tokio::test]
+async fn test_ptr_in_reverse_zone_round_trips() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    pdns.add_zone("0.0.10.in-addr.arpa", vec![]);
+    let cfg = pdns.config(&[]);
+
+    let changes = json!({ "create": [endpoint("1.0.0.10.in-addr.arpa", "PTR", &["host.example.com"])] });
+    let (status, body) = send(test_router(cfg.clone()), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{body}");
+    let ptr = &pdns.rrsets("0.0.10.in-addr.arpa")[0];
+    assert_eq!(ptr["name"], "1.0.0.10.in-addr.arpa.");
+    assert_eq!(ptr["records"][0]["content"], "host.example.com.");
+
+    let (_, listed) = send(test_router(cfg), "GET", "/records", None).await;
+    assert_eq!(listed[0]["dnsName"], "1.0.0.10.in-addr.arpa");
+    assert_eq!(listed[0]["recordType"], "PTR");
+    assert_eq!(listed[0]["targets"], json!(["host.example.com"]));
+}
+
+/// Delegation NS RRsets are listed and written like any other multi-valued
+/// record; the zone's own apex NS RRset is never reported.
+#[tokio::test]
+async fn test_ns_delegations_are_managed_apex_ns_is_not() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![
+        rrset("example.com", "NS", 3600, &["ns1.example.net.", "ns2.example.net."]),
+        rrset("dev.example.com", "NS", 300, &["ns1.dev.example.com."]),
+    ]);
+    let cfg = pdns.config(&[]);
+
+    let (_, listed) = send(test_router(cfg.clone()), "GET", "/records", None).await;
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    assert_eq!(listed[0]["dnsName"], "dev.example.com");
+    assert_eq!(listed[0]["recordType"], "NS");
+    assert_eq!(listed[0]["targets"], json!(["ns1.dev.example.com"]));
+
+    let changes = json!({
+        "create": [endpoint("team.example.com", "NS", &["ns-a.example.org", "ns-b.example.org"])],
+    });
+    let (status, body) = send(test_router(cfg), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{body}");
+    let team = pdns.rrsets("example.com").into_iter().find(|r| r["name"] == "team.example.com.").unwrap();
+    assert_eq!(team["records"][0]["content"], "ns-a.example.org.");
+    assert_eq!(team["records"][1]["content"], "ns-b.example.org.");
+}
+
+/// Overlapping applies to the same zone PATCH it one at a time, and both
+/// land.
+#[tokio::test]
+async fn test_concurrent_applies_to_one_zone_are_serialised() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    pdns.state.lock().unwrap().delay = Some(std::time::Duration::from_millis(100));
+    let app = test_router(pdns.config(&[]));
+
+    let apply = |name: &str| {
+        let changes = json!({ "create": [endpoint(name, "A", &["192.0.2.1"])] });
+        send(app.clone(), "POST", "/records", Some(changes))
+    };
+    let ((first, _), (second, _)) = tokio::join!(apply("a.example.com"), apply("b.example.com"));
+    assert_eq!(first, StatusCode::NO_CONTENT);
+    assert_eq!(second, StatusCode::NO_CONTENT);
+
+    assert_eq!(pdns.state.lock().unwrap().max_patches_in_flight, 1);
+    assert_eq!(pdns.patches().len(), 2);
+}
+
+/// /status stays empty until a read and an apply succeed, then reports them;
+/// a failed apply leaves the last success in place.
+#[tokio::test]
+async fn test_status_reports_last_successful_sync() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![rrset("www.example.com", "A", 300, &["192.0.2.1"])]);
+    let app = test_router(pdns.config(&[]));
+
+    let (_, body) = send(app.clone(), "GET", "/status", None).await;
+    assert_eq!(body, json!({
+        "lastApplySuccess": null,
+        "secondsSinceLastApplySuccess": null,
+        "endpointsLastRead": null,
+    }));
+
+    send(app.clone(), "GET", "/records", None).await;
+    let changes = json!({ "create": [endpoint("api.example.com", "A", &["192.0.2.2"])] });
+    let (status, _) = send(app.clone(), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (_, body) = send(app.clone(), "GET", "/status", None).await;
+    let last = body["lastApplySuccess"].as_u64().unwrap();
+    assert!(last > 0);
+    assert!(body["secondsSinceLastApplySuccess"].as_u64().unwrap() < 60);
+    assert_eq!(body["endpointsLastRead"], 1);
+
+    pdns.reject_patches("example.com");
+    let changes = json!({ "create": [endpoint("db.example.com", "A", &["192.0.2.3"])] });
+    let (status, _) = send(app.clone(), "POST", "/records", Some(changes)).await;
+    assert!(!status.is_success());
+    let (_, body) = send(app, "GET", "/status", None).await;
+    assert_eq!(body["lastApplySuccess"], last);
+}
+
+/// TLSA and SSHFP records are listed and written without a trailing dot.
+#[tokio::test]
+async fn test_tlsa_and_sshfp_round_trip() {
+    let hash = "2bb183af2f5f0e4c3c7e5e7b5f3d1e0f9a8b7c6d5e4f3a2b1c0d9e8f7a6b5c4d";
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![
+        rrset("host.example.com", "SSHFP", 300, &["4 2 0123456789abcdef"]),
+    ]);
+    let app = test_router(pdns.config(&[]));
+
+    let tlsa = format!("3 1 1 {}", hash.to_ascii_uppercase());
+    let changes = json!({ "create": [endpoint("_443._tcp.www.example.com", "TLSA", &[&tlsa])] });
+    let (status, body) = send(app.clone(), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{body}");
+    let stored = pdns.rrsets("example.com").into_iter().find(|r| r["type"] == "TLSA").unwrap();
+    assert_eq!(stored["records"][0]["content"], format!("3 1 1 {hash}"));
+
+    let (_, listed) = send(app, "GET", "/records", None).await;
+    let targets = |rtype: &str| {
+        let ep = listed.as_array().unwrap().iter().find(|e| e["recordType"] == rtype).unwrap();
+        ep["targets"].clone()
+    };
+    assert_eq!(targets("SSHFP"), json!(["4 2 0123456789abcdef"]));
+    assert_eq!(targets("TLSA"), json!([format!("3 1 1 {hash}")]));
+}
+
+/// An SRV record survives read → apply → read unchanged, with only its
+/// target field dotted.
+#[tokio::test]
+async fn test_srv_round_trip_is_stable() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone(
+        "example.com",
+        vec![rrset("_sip._tcp.example.com", "SRV", 300, &["10 60 5060 sipserver.example.com."])],
+    );
+    let cfg = pdns.config(&[]);
+
+    let (_, first) = send(test_router(cfg.clone()), "GET", "/records", None).await;
+    let srv = first.as_array().unwrap().iter().find(|ep| ep["recordType"] == "SRV").unwrap();
+    assert_eq!(srv["dnsName"], "_sip._tcp.example.com");
+
+    let changes = json!({ "updateOld": [srv], "updateNew": [srv] });
+    let (status, _) = send(test_router(cfg.clone()), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(
+        pdns.rrsets("example.com")[0]["records"][0]["content"],
+        "10 60 5060 sipserver.example.com."
+    );
+
+    let (_, second) = send(test_router(cfg), "GET", "/records", None).await;
+    assert_eq!(first, second);
+}
+
+/// SRV content from a source, without the trailing dot, is dotted only on
+/// the target field.
+#[tokio::test]
+async fn test_srv_normalises_target_field_only() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+
+    let changes = json!({
+        "create": [endpoint("_sip._tcp.example.com", "SRV", &["10 60 5060 sipserver.example.com"])],
+    });
+    let (_, body) = send(test_router(pdns.config(&[])), "POST", "/records/preview", Some(changes)).await;
+    assert_eq!(
+        body["zones"][0]["rrsets"][0]["records"][0]["content"],
+        "10 60 5060 sipserver.example.com."
+    );
+}
+
+/// GET /records only reports names under the filter on a label boundary.
+#[tokio::test]
+async fn test_get_records_filter_respects_label_boundaries() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![rrset("www.example.com", "A", 300, &["10.0.0.1"])]);
+    pdns.add_zone("notexample.com", vec![rrset("www.notexample.com", "A", 300, &["10.0.0.2"])]);
+
+    let cfg = pdns.config(&[("DOMAIN_FILTER", "example.com")]);
+    let (_, body) = send(test_router(cfg), "GET", "/records", None).await;
+    let names: Vec<&str> = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|ep| ep["dnsName"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["www.example.com"]);
+}
+
+/// EXCLUDE_DOMAINS carves subdomains out of an included zone for both reads
+/// and writes.
+#[tokio::test]
+async fn test_exclude_domains_within_included_zone() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![
+        rrset("www.example.com", "A", 300, &["10.0.0.1"]),
+        rrset("internal.example.com", "A", 300, &["10.0.0.2"]),
+        rrset("db.internal.example.com", "A", 300, &["10.0.0.3"]),
+    ]);
+
+    let cfg = pdns.config(&[
+        ("DOMAIN_FILTER", "example.com"),
+        ("EXCLUDE_DOMAINS", "internal.example.com"),
+    ]);
+    let (_, body) = send(test_router(cfg.clone()), "GET", "/", None).await;
+    assert_eq!(body, json!({ "include": ["example.com"], "exclude": ["internal.example.com"] }));
+
+    let (_, body) = send(test_router(cfg.clone()), "GET", "/records", None).await;
+    let names: Vec<&str> = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|ep| ep["dnsName"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["www.example.com"]);
+
+    let changes = json!({ "create": [endpoint("app.internal.example.com", "A", &["10.0.0.4"])] });
+    let (status, _) = send(test_router(cfg), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(pdns.patches().is_empty());
+}
+
+/// The regex filters restrict GET /records and are advertised in negotiation.
+#[tokio::test]
+async fn test_regex_domain_filter() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![
+        rrset("app.example.com", "A", 300, &["10.0.0.1"]),
+        rrset("app-canary.example.com", "A", 300, &["10.0.0.2"]),
+    ]);
+    pdns.add_zone("example.org", vec![rrset("app.example.org", "A", 300, &["10.0.0.3"])]);
+
+    let cfg = pdns.config(&[
+        ("DOMAIN_FILTER_REGEX", r"^app.*\.example\.(com|org)$"),
+        ("DOMAIN_FILTER_REGEX_EXCLUSION", "canary"),
+    ]);
+    let (_, body) = send(test_router(cfg.clone()), "GET", "/", None).await;
+    assert_eq!(body["regex"], r"^app.*\.example\.(com|org)$");
+    assert_eq!(body["regexExclusion"], "canary");
+
+    let (_, body) = send(test_router(cfg), "GET", "/records", None).await;
+    let mut names: Vec<&str> = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|ep| ep["dnsName"].as_str().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["app.example.com", "app.example.org"]);
+}
+
+/// A CNAME written through upsert (dotted in PowerDNS) reads back without
+/// the trailing dot, as external-dns stores it.
+#[tokio::test]
+async fn test_cname_reads_back_without_trailing_dot() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    let cfg = pdns.config(&[]);
+
+    let changes = json!({ "create": [endpoint("www.example.com", "CNAME", &["lb.example.com"])] });
+    let (status, _) = send(test_router(cfg.clone()), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(pdns.rrsets("example.com")[0]["records"][0]["content"], "lb.example.com.");
+
+    let (_, body) = send(test_router(cfg), "GET", "/records", None).await;
+    assert_eq!(body[0]["targets"], json!(["lb.example.com"]));
+}
+
+/// Provider-specific properties and the set identifier are stored in an
+/// RRset comment on write and restored on read.
+#[tokio::test]
+async fn test_provider_specific_round_trips_through_comment() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    let cfg = pdns.config(&[]);
+
+    let mut ep = endpoint("app.example.com", "HTTPS", &["1 . alpn=h2"]);
+    ep["providerSpecific"] = json!([{ "name": "webhook/pdns-https-target", "value": "1 . alpn=h2" }]);
+    ep["setIdentifier"] = json!("eu-west");
+    let changes = json!({ "create": [ep] });
+    let (status, body) = send(test_router(cfg.clone()), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{body}");
+    assert_eq!(pdns.rrsets("example.com")[0]["comments"][0]["account"], "external-dns-meta");
+
+    let (_, body) = send(test_router(cfg.clone()), "GET", "/records", None).await;
+    assert_eq!(body[0]["providerSpecific"][0]["name"], "webhook/pdns-https-target");
+    assert_eq!(body[0]["providerSpecific"][0]["value"], "1 . alpn=h2");
+    assert_eq!(body[0]["setIdentifier"], "eu-west");
+
+    // A comment someone mangled in the PowerDNS UI is ignored, not fatal.
+    let mut mangled = rrset("www.example.com", "A", 300, &["10.0.0.1"]);
+    mangled["comments"] = json!([{ "content": "{not json", "account": "external-dns-meta" }]);
+    pdns.add_zone("example.com", vec![mangled]);
+
+    let (status, body) = send(test_router(cfg), "GET", "/records", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body[0]["labels"], json!({}));
+    assert_eq!(body[0]["providerSpecific"], json!([]));
+    assert_eq!(body[0]["setIdentifier"], "");
+}
+
+/// Endpoint labels (the TXT registry's ownership data) are kept in the meta
+/// comment, sorted, and restored on read.
+#[tokio::test]
+async fn test_labels_round_trip_through_comment() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    let cfg = pdns.config(&[]);
+
+    let mut ep = endpoint("www.example.com", "A", &["10.0.0.1"]);
+    ep["labels"] = json!({ "resource": "ingress/default/web", "owner": "cluster-a" });
+    let (status, _) = send(test_router(cfg.clone()), "POST", "/records", Some(json!({ "create": [ep] }))).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(
+        pdns.rrsets("example.com")[0]["comments"][0]["content"],
+        r#"{"labels":{"owner":"cluster-a","resource":"ingress/default/web"}}"#
+    );
+
+    let (_, body) = send(test_router(cfg), "GET", "/records", None).await;
+    assert_eq!(body[0]["labels"], json!({ "owner": "cluster-a", "resource": "ingress/default/web" }));
+}
+
+/// Dropping an endpoint's labels clears the meta comment: the REPLACE always
+/// carries a comments key, since PowerDNS keeps the old comments otherwise.
+#[tokio::test]
+async fn test_removed_labels_clear_meta_comment() {
+    let pdns = MockPdns::start().await;
+    let mut stored = rrset("www.example.com", "A", 300, &["10.0.0.1"]);
+    let meta = r#"{"labels":{"owner":"cluster-a"}}"#;
+    stored["comments"] = json!([{ "content": meta, "account": "external-dns-meta" }]);
+    pdns.add_zone("example.com", vec![stored]);
+
+    let mut old = endpoint("www.example.com", "A", &["10.0.0.1"]);
+    old["labels"] = json!({ "owner": "cluster-a" });
+    let new = endpoint("www.example.com", "A", &["10.0.0.2"]);
+    let changes = json!({ "updateOld": [old], "updateNew": [new] });
+    let (status, body) = send(test_router(pdns.config(&[])), "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{body}");
+
+    assert_eq!(pdns.patches()[0]["rrsets"][0]["comments"], json!([]));
+    assert!(pdns.rrsets("example.com")[0]["comments"].as_array().unwrap().is_empty());
+}
+
+/// Once a removed meta comment has been cleared, the next sync of the same
+/// endpoint finds the RRset unchanged and sends no PATCH.
+#[tokio::test]
+async fn test_sync_after_meta_removal_is_unchanged() {
+    let pdns = MockPdns::start().await;
+    let mut stored = rrset("www.example.com", "A", 300, &["10.0.0.1"]);
+    let meta = r#"{"labels":{"owner":"cluster-a"}}"#;
+    stored["comments"] = json!([{ "content": meta, "account": "external-dns-meta" }]);
+    pdns.add_zone("example.com", vec![stored]);
+    let cfg = pdns.config(&[]);
+
+    let changes = json!({ "create": [endpoint("www.example.com", "A", &["10.0.0.1"])] });
+    for _ in 0..2 {
+        let (status, body) =
+            send(test_router(cfg.clone()), "POST", "/records", Some(changes.clone())).await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{body}");
+    }
+
+    assert_eq!(pdns.patches().len(), 1, "{:?}", pdns.patches());
+    assert_eq!(pdns.patches()[0]["rrsets"][0]["comments"], json!([]));
+}
+
+/// The debug zone list shows every hosted zone and how the domain and
+/// account filters treat it.
+#[tokio::test]
+async fn test_debug_zones_reports_filter_matches() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    pdns.add_zone("example.org", vec![]);
+    pdns.add_zone("other.example.com", vec![]);
+    pdns.set_account("other.example.com", "team-b");
+
+    let cfg = pdns.config(&[
+        ("DOMAIN_FILTER", "app.example.com,other.example.com"),
+        ("PDNS_ACCOUNT_FILTER", "team-b"),
+    ]);
+    let (status, body) = send(test_router(cfg), "GET", "/zones", None).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["domainFilter"], json!(["app.example.com", "other.example.com"]));
+    assert_eq!(
+        body["zones"],
+        json!([
+            { "id": "example.com.", "name": "example.com.", "account": "", "kind": "Native", "server": "localhost",
+              "matchesDomainFilter": true, "accountAllowed": false },
+            { "id": "example.org.", "name": "example.org.", "account": "", "kind": "Native", "server": "localhost",
+              "matchesDomainFilter": false, "accountAllowed": false },
+            { "id": "other.example.com.", "name": "other.example.com.", "account": "team-b", "kind": "Native", "server": "localhost",
+              "matchesDomainFilter": true, "accountAllowed": true },
+        ])
+    );
+}
+
+/// Two RRsets whose names only differ in case are listed as one endpoint
+/// with the union of their targets and the lower TTL.
+#[tokio::test]
+async fn test_list_merges_duplicate_rrsets() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![
+        rrset("www.example.com", "A", 300, &["10.0.0.1", "10.0.0.2"]),
+        rrset("WWW.example.com", "A", 60, &["10.0.0.2", "10.0.0.3"]),
+        rrset("www.example.com", "TXT", 300, &["\"hello\""]),
+    ]);
+
+    let (status, body) = send(test_router(pdns.config(&[])), "GET", "/records", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let eps = body.as_array().unwrap();
+    assert_eq!(eps.len(), 2, "{body}");
+    let a = eps.iter().find(|ep| ep["recordType"] == "A").unwrap();
+    assert_eq!(a["dnsName"], "www.example.com");
+    assert_eq!(a["targets"], json!(["10.0.0.1", "10.0.0.2", "10.0.0.3"]));
+    assert_eq!(a["recordTTL"], 60);
+}
+
+/// GET /records lists endpoints by name then type, with sorted targets,
+/// whatever order the zones and records come in.
+#[tokio::test]
+async fn test_get_records_is_sorted() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.net", vec![rrset("a.example.net", "A", 300, &["10.0.0.1"])]);
+    pdns.add_zone("example.com", vec![
+        rrset("www.example.com", "TXT", 300, &["\"b\"", "\"a\""]),
+        rrset("www.example.com", "A", 300, &["10.0.0.9", "10.0.0.10", "10.0.0.2"]),
+        rrset("api.example.com", "A", 300, &["10.0.0.3"]),
+    ]);
+
+    let cfg = pdns.config(&[]);
+    let (_, first) = send(test_router(cfg.clone()), "GET", "/records", None).await;
+    let listed: Vec<(&str, &str)> = first
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|ep| (ep["dnsName"].as_str().unwrap(), ep["recordType"].as_str().unwrap()))
+        .collect();
+    assert_eq!(listed, [
+        ("a.example.net", "A"),
+        ("api.example.com", "A"),
+        ("www.example.com", "A"),
+        ("www.example.com", "TXT"),
+    ]);
+    assert_eq!(first[2]["targets"], json!(["10.0.0.10", "10.0.0.2", "10.0.0.9"]));
+    assert_eq!(first[3]["targets"], json!(["a", "b"]));
+
+    let (_, second) = send(test_router(cfg), "GET", "/records", None).await;
+    assert_eq!(first, second);
+}
+
+/// A sync stuck on a hung PowerDNS is cut off with 504 after
+/// HANDLER_TIMEOUT_SECS instead of holding the connection open.
+#[tokio::test]
+async fn test_handler_timeout_answers_504() {
+    let pdns = MockPdns::start().await;
+    pdns.add_zone("example.com", vec![]);
+    pdns.state.lock().unwrap().delay = Some(std::time::Duration::from_secs(5));
+
+    let cfg = pdns.config(&[("HANDLER_TIMEOUT_SECS", "1")]);
+    let state = AppState::new(cfg.clone(), PdnsClient::new(cfg).unwrap());
+    let app = Router::new()
+        .route("/records", post(handlers::apply_changes))
+        .route_layer(middleware::from_fn_with_state(state.clone(), handlers::handler_timeout))
+        .with_state(state);
+
+    let changes = json!({
+        "create": [{ "dnsName": "www.example.com", "recordType": "A", "targets": ["10.0.0.1"] }],
+    });
+    let (status, body) = send(app, "POST", "/records", Some(changes)).await;
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(body["error"], "POST /records timed out after 1s");
+}
@@ -0,0 +1,104 @@
+// tests/router.rs
+//
+// Tests for the middleware stack `pdns_webhook::app` puts in front of the
+// handlers: body limits, request body logging and panic handling.
+//
+// Run:
+//   cargo test --test router
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use pdns_webhook::{config::Config, pdns::PdnsClient, router::render_body, AppState};
+use tower::ServiceExt; // for `.oneshot()`
+
+fn app(extra: &[(&str, &str)]) -> Router {
+    let mut vars = vec![
+        ("PDNS_API_KEY".to_string(), "secret".to_string()),
+        ("PDNS_API_KEY_FILE".to_string(), "/nonexistent".to_string()),
+    ];
+    vars.extend(extra.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+    let cfg = Config::from_vars(vars).unwrap();
+    pdns_webhook::app(AppState::new(cfg.clone(), PdnsClient::new(cfg).unwrap()))
+}
+
+/// A body streamed in `chunks` pieces of `size` bytes, with no
+/// Content-Length, so only reading it reveals its length.
+fn chunked(chunks: usize, size: usize) -> Body {
+    let pieces = (0..chunks).map(move |_| Ok::<_, std::io::Error>(vec![b' '; size]));
+    Body::from_stream(futures::stream::iter(pieces))
+}
+
+/// JSON bodies are logged pretty-printed; anything else as it came.
+#[test]
+fn test_render_body_pretty_prints_json() {
+    assert_eq!(render_body(br#"{"create":[]}"#, 0), "{\n  \"create\": []\n}");
+    assert_eq!(render_body(b"not json", 0), "not json");
+    assert_eq!(render_body(&[0xff, 0xfe], 0), "<2 binary bytes>");
+}
+
+/// Bodies over LOG_BODY_MAX_BYTES are cut at the limit and marked with
+/// their full length; at the limit, or with no limit, they are logged whole.
+#[test]
+fn test_render_body_truncates_past_limit() {
+    let body = br#"{"create":[{"dnsName":"www.example.com"}]}"#;
+    assert_eq!(render_body(body, 9), format!("{{\"create\"… <{} bytes, truncated>", body.len()));
+    assert!(!render_body(body, body.len()).contains("truncated"));
+    assert!(!render_body(body, 0).contains("truncated"));
+}
+
+/// The body is only read after the bearer token is checked, so an
+/// unauthenticated client can't make the webhook buffer a large body.
+#[tokio::test]
+async fn test_body_is_not_read_before_auth() {
+    let app = app(&[("WEBHOOK_AUTH_TOKEN", "s3cret"), ("MAX_BODY_BYTES", "1024")]);
+    let request = Request::builder()
+        .method("POST")
+        .uri("/records")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(chunked(8, 512))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// A body over MAX_BODY_BYTES is refused with 413 whether its length is
+/// declared up front or only found while streaming it.
+#[tokio::test]
+async fn test_oversized_body_is_rejected() {
+    let app = app(&[("MAX_BODY_BYTES", "1024")]);
+    let post = |body: Body| {
+        Request::builder()
+            .method("POST")
+            .uri("/records")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .unwrap()
+    };
+
+    let sized = app.clone().oneshot(post(Body::from(vec![b' '; 2048]))).await.unwrap();
+    assert_eq!(sized.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let streamed = app.clone().oneshot(post(chunked(8, 512))).await.unwrap();
+    assert_eq!(streamed.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    // Under the limit the body reaches the handler, which rejects it as JSON.
+    let small = app.oneshot(post(chunked(2, 256))).await.unwrap();
+    assert_eq!(small.status(), StatusCode::BAD_REQUEST);
+}
+
+/// A panicking handler answers 500 with a JSON error rather than dropping
+/// the connection.  The route only exists with the `test-panic` feature,
+/// which the crate's dev-dependency on itself turns on.
+#[tokio::test]
+async fn test_panic_answers_500() {
+    let request = Request::builder().uri("/test/panic").body(Body::empty()).unwrap();
+    let response = app(&[]).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["error"], "internal error");
+}
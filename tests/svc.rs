@@ -0,0 +1,60 @@
+// tests/svc.rs
+//
+// Tests for the shared HTTPS/SVCB target helpers and the record content
+// normalisation built on them.
+//
+// Run:
+//   cargo test --test svc
+
+use pdns_webhook::{
+    pdns::{denormalise_target, normalise_target},
+    svc::{check_svc_params, check_svc_target, normalise_svc_target},
+};
+
+/// Every shorthand form gets a SvcPriority and a fully qualified TargetName.
+#[test]
+fn test_normalise_svc_target_forms() {
+    let cases = [
+        ("1 . alpn=h2,h3",            "1 . alpn=h2,h3"),
+        ("1 lb.example.com alpn=h2",  "1 lb.example.com. alpn=h2"),
+        ("lb.example.com",            "1 lb.example.com."),
+        ("lb.example.com alpn=h2",    "1 lb.example.com. alpn=h2"),
+        ("alpn=h2,h3",                "1 . alpn=h2,h3"),
+        ("no-default-alpn alpn=h3",   "1 . no-default-alpn alpn=h3"),
+        ("  0 svc.example.com.  ",    "0 svc.example.com."),
+    ];
+    for (target, want) in cases {
+        assert_eq!(normalise_svc_target(target), want, "{target}");
+    }
+}
+
+/// Normalising twice changes nothing, so what /adjustendpoints hands back is
+/// exactly what is written and later read back.
+#[test]
+fn test_normalise_svc_target_is_idempotent() {
+    for target in ["alpn=h2", "lb.example.com port=8443", "2 . ipv4hint=192.0.2.1"] {
+        let once = normalise_svc_target(target);
+        assert_eq!(normalise_svc_target(&once), once);
+        assert_eq!(denormalise_target("HTTPS", &normalise_target("HTTPS", &once)), once);
+    }
+}
+
+/// SSHFP and TLSA content is never treated as a name: no dot is appended,
+/// only the hex is lowercased.
+#[test]
+fn test_sshfp_and_tlsa_content_is_not_dotted() {
+    let tlsa = "3 1 1 2BB183AF2F5F0E4C3C7E5E7B5F3D1E0F9A8B7C6D5E4F3A2B1C0D9E8F7A6B5C4D";
+    assert_eq!(normalise_target("TLSA", tlsa), tlsa.to_ascii_lowercase());
+    assert_eq!(normalise_target("SSHFP", " 4 2 abcdef0123 "), "4 2 abcdef0123");
+    assert_eq!(denormalise_target("TLSA", &tlsa.to_ascii_lowercase()), tlsa.to_ascii_lowercase());
+}
+
+#[test]
+fn test_check_svc_target() {
+    assert!(check_svc_target("1 . alpn=h2").is_ok());
+    assert!(check_svc_target("1 lb.example.com. port=443").is_ok());
+    assert!(check_svc_target("x . alpn=h2").is_err());
+    assert!(check_svc_target("1 bad_host!. alpn=h2").is_err());
+    assert!(check_svc_params("1 . alnp=h2").unwrap_err().contains("alnp"));
+    assert!(check_svc_params("1 . port=99999").is_err());
+}